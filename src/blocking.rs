@@ -0,0 +1,281 @@
+use std::{collections::HashMap, pin::Pin, sync::Arc};
+use futures_util::{Stream, StreamExt};
+use serde_json::Value;
+use tokio::runtime::{self, Runtime};
+
+use crate::{client::*, methods::*, paginator::Paginator, requester::*, types::{character::*, chat::*, enums::Visibility, ids::*, media::*, user::*, ws::*}};
+
+/// A synchronous [`AsyncClient`] for code that doesn't run an async runtime, such as build scripts and simple
+/// CLIs. It owns a small tokio runtime and blocks on it for every call.
+///
+/// It must not be used from inside an async runtime: blocking calls panic there.
+pub struct BlockingClient {
+    // Dropped before the runtime, so the websocket is closed while its tasks can still run.
+    client: Arc<AsyncClient>,
+    runtime: Runtime,
+}
+
+impl BlockingClient {
+    pub fn new(token: impl Into<SecretToken>) -> Result<Self, RequesterError> {
+        Self::from_builder(AsyncClient::builder().with_token(token))
+    }
+
+    /// Builds the client described by `builder`, failing like [`AsyncClientBuilder::build`] does.
+    pub fn from_builder(builder: AsyncClientBuilder) -> Result<Self, RequesterError> {
+        let runtime = runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .map_err(|err| RequesterError::RequestFailed(format!("could not start the runtime: {}", err)))?;
+        let client = runtime.block_on(builder.build())?;
+
+        Ok(Self { client, runtime })
+    }
+
+    /// The async client behind this one.
+    pub fn async_client(&self) -> &Arc<AsyncClient> {
+        &self.client
+    }
+
+    pub fn token(&self) -> Result<SecretToken, RequesterError> {
+        self.runtime.block_on(self.client.token())
+    }
+
+    pub fn data(&self) -> Account {
+        self.runtime.block_on(self.client.data())
+    }
+
+    pub fn set_token(&self, token: impl Into<SecretToken>) {
+        self.runtime.block_on(self.client.set_token(token))
+    }
+
+    pub fn set_data(&self, account: impl Into<Account>) {
+        self.runtime.block_on(self.client.set_data(account))
+    }
+
+    pub fn close_session(&self) {
+        self.runtime.block_on(self.client.close_session())
+    }
+
+    pub fn session_health(&self) -> WsHealth {
+        self.runtime.block_on(self.client.session_health())
+    }
+
+    pub fn raw_request(&self, method: impl Into<String>, host: Host, path: impl AsRef<str>, body: Option<Value>) -> Result<Value, RequesterError> {
+        self.runtime.block_on(self.client.raw_request(method, host, path, body))
+    }
+
+    pub fn raw_ws_command(&self, command: &str, payload: Value) -> Result<BlockingIter<'_, Result<WsEvent, RequesterError>>, RequesterError> {
+        let stream = self.runtime.block_on(self.client.raw_ws_command(command, payload))?;
+        Ok(BlockingIter::new(&self.runtime, stream))
+    }
+
+    /// Iterates over a [`Paginator`] from one of the `*_stream` methods of [`Self::async_client`], blocking for
+    /// each page.
+    pub fn paginate<T: 'static>(&self, paginator: Paginator<T>) -> BlockingIter<'_, Result<T, RequesterError>> {
+        BlockingIter::new(&self.runtime, paginator)
+    }
+
+    pub fn account(&self) -> BlockingAccountMethods<'_> {
+        BlockingAccountMethods { runtime: &self.runtime, inner: self.client.account() }
+    }
+
+    pub fn user(&self) -> BlockingUserMethods<'_> {
+        BlockingUserMethods { runtime: &self.runtime, inner: self.client.user() }
+    }
+
+    pub fn chat(&self) -> BlockingChatMethods<'_> {
+        BlockingChatMethods { runtime: &self.runtime, inner: self.client.chat() }
+    }
+
+    pub fn character(&self) -> BlockingCharacterMethods<'_> {
+        BlockingCharacterMethods { runtime: &self.runtime, inner: self.client.character() }
+    }
+
+    pub fn utils(&self) -> BlockingUtilsMethods<'_> {
+        BlockingUtilsMethods { runtime: &self.runtime, inner: self.client.utils() }
+    }
+}
+
+/// A stream of a [`BlockingClient`] as an iterator, blocking for each item.
+pub struct BlockingIter<'a, T> {
+    runtime: &'a Runtime,
+    stream: Pin<Box<dyn Stream<Item = T> + 'a>>,
+}
+
+impl<'a, T> BlockingIter<'a, T> {
+    fn new(runtime: &'a Runtime, stream: impl Stream<Item = T> + 'a) -> Self {
+        Self { runtime, stream: Box::pin(stream) }
+    }
+}
+
+impl<T> Iterator for BlockingIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.runtime.block_on(self.stream.next())
+    }
+}
+
+/// Declares a method group of [`BlockingClient`] that blocks on the async method of the same name for each of
+/// the listed methods.
+macro_rules! blocking_methods {
+    ($(#[$doc:meta])* $name:ident($inner:ty) { $($(#[$attr:meta])* fn $method:ident $(<$lt:lifetime>)? (&self $(, $arg:ident: $ty:ty)*) -> $ret:ty;)* }) => {
+        $(#[$doc])*
+        pub struct $name<'a> {
+            runtime: &'a Runtime,
+            inner: &'a $inner,
+        }
+
+        impl $name<'_> {
+            $(
+                $(#[$attr])*
+                pub fn $method $(<$lt>)? (&self $(, $arg: $ty)*) -> $ret {
+                    self.runtime.block_on(self.inner.$method($($arg),*))
+                }
+            )*
+        }
+    };
+}
+
+blocking_methods! {
+    /// The blocking counterpart of [`AccountMethods`].
+    BlockingAccountMethods(AccountMethods) {
+        fn fetch_profile(&self) -> Result<Account, RequesterError>;
+        fn fetch_settings(&self) -> Result<Settings, RequesterError>;
+        fn fetch_followers(&self) -> Result<Vec<String>, RequesterError>;
+        fn fetch_following(&self) -> Result<Vec<String>, RequesterError>;
+        fn fetch_persona(&self, id: impl AsRef<PersonaIdRef>) -> Result<Persona, RequesterError>;
+        fn fetch_personas(&self) -> Result<Vec<Persona>, RequesterError>;
+        fn fetch_characters(&self) -> Result<Vec<PartialCharacter>, RequesterError>;
+        fn fetch_characters_ranked(&self) -> Result<Vec<PartialCharacter>, RequesterError>;
+        fn fetch_voices(&self) -> Result<Vec<Voice>, RequesterError>;
+        fn edit_account<'s>(&self, name: impl Into<&'s String>, username: impl Into<&'s String>, bio: Option<&String>, avatar_path: Option<&String>) -> Result<bool, RequesterError>;
+        fn create_persona<'s>(&self, name: impl Into<&'s String>, definition: Option<String>, avatar_path: Option<String>) -> Result<Persona, RequesterError>;
+        fn edit_persona(&self, id: impl AsRef<PersonaIdRef>, name: Option<&String>, definition: Option<&String>, avatar_path: Option<&String>, persona: Option<&Persona>) -> Result<Persona, RequesterError>;
+        fn delete_persona(&self, id: impl AsRef<PersonaIdRef>, persona: Option<&Persona>) -> Result<Persona, RequesterError>;
+        fn set_default_persona(&self, id: Option<&PersonaIdRef>, settings: Option<&mut Settings>) -> bool;
+        fn set_persona(&self, character_id: impl AsRef<CharacterIdRef>, persona_id: Option<&PersonaIdRef>, settings: Option<&mut Settings>) -> bool;
+        fn set_voice(&self, voice_id: Option<&VoiceIdRef>, settings: Option<&mut Settings>) -> bool;
+    }
+}
+
+blocking_methods! {
+    /// The blocking counterpart of [`UserMethods`].
+    BlockingUserMethods(UserMethods) {
+        fn fetch_user<'s>(&self, username: impl Into<&'s String>) -> Result<User, RequesterError>;
+        fn fetch_user_voices<'s>(&self, username: impl Into<&'s String>) -> Result<Vec<Voice>, RequesterError>;
+        fn follow_user<'s>(&self, username: impl Into<&'s String>) -> bool;
+        fn unfollow_user<'s>(&self, username: impl Into<&'s String>, token: Option<SecretToken>) -> bool;
+    }
+}
+
+blocking_methods! {
+    /// The blocking counterpart of [`ChatMethods`]. Streaming methods return a [`BlockingIter`].
+    BlockingChatMethods(ChatMethods) {
+        fn fetch_histories(&self, character_id: impl AsRef<CharacterIdRef>, amount: usize) -> Result<Vec<ChatHistory>, RequesterError>;
+        fn fetch_chats(&self, character_id: impl AsRef<CharacterIdRef>, num_preview_turns: usize) -> Result<Vec<Chat>, RequesterError>;
+        fn fetch_chat(&self, chat_id: impl AsRef<ChatIdRef>) -> Result<Chat, RequesterError>;
+        fn fetch_recent_chats(&self) -> Result<Vec<Chat>, RequesterError>;
+        fn fetch_messages(&self, chat_id: impl AsRef<ChatIdRef>, pinned_only: bool, next_token: Option<String>) -> Result<(Vec<Turn>, Option<String>), RequesterError>;
+        fn fetch_all_messages(&self, chat_id: impl AsRef<ChatIdRef>, pinned_only: bool) -> Result<Vec<Turn>, RequesterError>;
+        fn fetch_following_messages(&self, chat_id: impl AsRef<ChatIdRef>, turn_id: impl AsRef<TurnIdRef>, pinned_only: bool) -> Result<Vec<Turn>, RequesterError>;
+        fn update_chat_name<'s>(&self, chat_id: impl AsRef<ChatIdRef>, name: impl Into<&'s String>) -> bool;
+        fn archive_chat(&self, chat_id: impl AsRef<ChatIdRef>) -> bool;
+        fn unarchive_chat(&self, chat_id: impl AsRef<ChatIdRef>) -> bool;
+        fn copy_chat(&self, chat_id: impl AsRef<ChatIdRef>, end_turn_id: impl AsRef<TurnIdRef>) -> Result<Option<ChatId>, RequesterError>;
+        fn create_chat(&self, character_id: impl AsRef<CharacterIdRef>, greeting: bool, model_type: Option<String>) -> Result<(Chat, Option<Turn>), RequesterError>;
+        fn update_primary_candidate(&self, chat_id: impl AsRef<ChatIdRef>, turn_id: impl AsRef<TurnIdRef>, candidate_id: impl AsRef<CandidateIdRef>) -> bool;
+        fn send_message<'s>(&self, character_id: impl AsRef<CharacterIdRef>, chat_id: impl AsRef<ChatIdRef>, text: impl Into<&'s String>) -> Result<Turn, RequesterError>;
+        fn retry_response(&self, character_id: impl AsRef<CharacterIdRef>, chat_id: impl AsRef<ChatIdRef>, turn_id: impl AsRef<TurnIdRef>) -> Result<Turn, RequesterError>;
+        fn edit_message<'s>(&self, chat_id: impl AsRef<ChatIdRef>, turn_id: impl AsRef<TurnIdRef>, candidate_id: impl AsRef<CandidateIdRef>, text: impl Into<&'s String>) -> Result<Turn, RequesterError>;
+        fn delete_messages(&self, chat_id: impl AsRef<ChatIdRef>, turn_ids: impl IntoIterator<Item = impl AsRef<TurnIdRef>>) -> bool;
+        fn set_turn_pin(&self, chat_id: impl AsRef<ChatIdRef>, turn_id: impl AsRef<TurnIdRef>, is_pinned: bool) -> bool;
+    }
+}
+
+impl<'a> BlockingChatMethods<'a> {
+    pub fn default_annotations(&self) -> Value {
+        self.inner.default_annotations()
+    }
+
+    pub fn send_command(&self, command: &WsCommand) -> Result<BlockingIter<'a, Result<WsEvent, RequesterError>>, RequesterError> {
+        let stream = self.runtime.block_on(self.inner.send_command(command))?;
+        Ok(BlockingIter::new(self.runtime, stream))
+    }
+
+    pub fn send_message_stream<'s>(&'s self, character_id: impl AsRef<CharacterIdRef> + 's, chat_id: impl AsRef<ChatIdRef> + 's, text: impl Into<&'s String> + 's) -> Result<BlockingIter<'s, Result<Turn, RequesterError>>, RequesterError> {
+        let stream = self.runtime.block_on(self.inner.send_message_stream(character_id, chat_id, text))?;
+        Ok(BlockingIter::new(self.runtime, stream))
+    }
+
+    pub fn retry_response_stream<'s>(&'s self, character_id: impl AsRef<CharacterIdRef> + 's, chat_id: impl AsRef<ChatIdRef> + 's, turn_id: impl AsRef<TurnIdRef> + 's) -> Result<BlockingIter<'s, Result<Turn, RequesterError>>, RequesterError> {
+        let stream = self.runtime.block_on(self.inner.retry_response_stream(character_id, chat_id, turn_id))?;
+        Ok(BlockingIter::new(self.runtime, stream))
+    }
+}
+
+blocking_methods! {
+    /// The blocking counterpart of [`CharacterMethods`].
+    BlockingCharacterMethods(CharacterMethods) {
+        fn fetch_characters_by_category(&self) -> Result<HashMap<String, Vec<PartialCharacter>>, RequesterError>;
+        fn fetch_recommended_characters(&self) -> Result<Vec<PartialCharacter>, RequesterError>;
+        fn fetch_featured_characters(&self) -> Result<Vec<PartialCharacter>, RequesterError>;
+        fn fetch_similar_characters(&self, character_id: impl AsRef<CharacterIdRef>) -> Result<Vec<PartialCharacter>, RequesterError>;
+        fn fetch_character_info(&self, character_id: impl AsRef<CharacterIdRef>) -> Result<Character, RequesterError>;
+        fn search_characters<'s>(&self, character_name: impl Into<&'s String>) -> Result<Vec<PartialCharacter>, RequesterError>;
+        fn search_creators<'s>(&self, creator_name: impl Into<&'s String>) -> Result<Vec<String>, RequesterError>;
+        fn add_like_to_character(&self, character_id: impl AsRef<CharacterIdRef>, like: Option<bool>) -> bool;
+        #[allow(clippy::too_many_arguments)]
+        fn create_character<'s>(&self, name: impl Into<&'s String>, greeting: impl Into<&'s String>, title: impl Into<&'s String>, description: impl Into<&'s String>, definition: impl Into<&'s String>, copyable: bool, visibility: Visibility, avatar_rel_path: impl Into<&'s String>, default_voice_id: impl AsRef<VoiceIdRef>) -> Result<Character, RequesterError>;
+        #[allow(clippy::too_many_arguments)]
+        fn edit_character<'s>(&self, character_id: impl AsRef<CharacterIdRef>, name: impl Into<&'s String>, greeting: impl Into<&'s String>, title: impl Into<&'s String>, description: impl Into<&'s String>, definition: impl Into<&'s String>, copyable: bool, visibility: Visibility, avatar_rel_path: impl Into<&'s String>, default_voice_id: impl AsRef<VoiceIdRef>) -> Result<Character, RequesterError>;
+    }
+}
+
+blocking_methods! {
+    /// The blocking counterpart of [`UtilsMethods`].
+    BlockingUtilsMethods(UtilsMethods) {
+        fn fetch_voice(&self, voice_id: impl AsRef<VoiceIdRef>) -> Result<Voice, RequesterError>;
+        fn search_voices<'s>(&self, voice_name: impl Into<&'s String>) -> Result<Vec<Voice>, RequesterError>;
+        fn generate_image(&self, prompt: &str, num_candidates: Option<u8>) -> Result<Vec<String>, RequesterError>;
+        fn upload_avatar(&self, data: Vec<u8>, mime_type: String, check_image: bool) -> Result<Avatar, RequesterError>;
+        fn upload_voice<'s>(&self, data: Vec<u8>, mime_type: String, name: impl Into<&'s String>, description: Option<String>, visibility: Option<Visibility>) -> Result<Voice, RequesterError>;
+        fn edit_voice(&self, voice: impl Into<VoiceOrId>, name: Option<String>, description: Option<String>, visibility: Option<Visibility>) -> Result<Voice, RequesterError>;
+        fn delete_voice(&self, voice_id: impl AsRef<VoiceIdRef>) -> bool;
+        fn generate_speech(&self, chat_id: impl AsRef<ChatIdRef>, turn_id: impl AsRef<TurnIdRef>, candidate_id: impl AsRef<CandidateIdRef>, voice_id: impl AsRef<VoiceIdRef>, return_url: bool) -> Result<Result<Vec<u8>, String>, RequesterError>;
+        fn ping(&self) -> bool;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn blocking_client_offline() {
+        let transport = MemoryTransport::new();
+        transport.respond_json("GET", "/chat/user/", json!({ "user": { "user": { "id": 1, "username": "someone", "account": { "name": "Someone" } } } }));
+        transport.on_ws_command(|command| ["Hel", "Hello!"].iter().enumerate().map(|(i, text)| json!({
+            "command": "update_turn",
+            "request_id": command["request_id"],
+            "turn": {
+                "turn_key": { "chat_id": "chat", "turn_id": "reply" },
+                "author": { "author_id": "2", "name": "Bot" },
+                "primary_candidate_id": "c",
+                "candidates": [{ "candidate_id": "c", "raw_content": text, "is_final": i == 1 }]
+            }
+        })).collect());
+        let client = BlockingClient::from_builder(AsyncClient::builder().with_token("token").with_transport(transport)).unwrap();
+        assert_eq!(client.data().username, "someone");
+        assert_eq!(client.account().fetch_profile().unwrap().name, "Someone");
+
+        let (character, chat, text) = ("character".to_string(), "chat".to_string(), "Hi".to_string());
+        let texts = client.chat().send_message_stream(&character, &chat, &text).unwrap()
+            .map(|turn| turn.unwrap().get_primary_candidate().unwrap().text.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["Hel", "Hello!"]);
+    }
+}
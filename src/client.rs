@@ -1,109 +1,149 @@
-use std::{sync::Arc, collections::HashMap};
+use std::{sync::Arc, collections::HashMap, time::Duration};
+use futures_util::Stream;
+use serde_json::{json, Value};
 use tokio::sync::RwLock;
 
-use crate::{methods::*, requester::*, types::user::Account};
+use crate::{methods::*, requester::*, types::{user::Account, ws::{WsCommand, WsEvent}}};
 
+/// The token and profile of a client, shared with its method groups.
+///
+/// Method groups hold this rather than the [`AsyncClient`] itself, so they don't keep the client alive.
+#[derive(Debug, Default)]
+pub(crate) struct ClientState {
+    token: RwLock<Option<SecretToken>>,
+    /// Asked for the token instead of `token` when set.
+    provider: Option<Arc<dyn TokenProvider>>,
+    data: RwLock<Account>,
+}
+
+impl ClientState {
+    pub async fn token(&self) -> Result<SecretToken, RequesterError> {
+        match &self.provider {
+            Some(provider) => provider.token().await,
+            None => self.token.read().await.clone().ok_or(RequesterError::MissingToken),
+        }
+    }
+
+    pub async fn data(&self) -> Account {
+        self.data.read().await.clone()
+    }
+
+    pub(crate) async fn get_headers(&self, token: Option<SecretToken>) -> Result<HashMap<String, String>, RequesterError> {
+        let tokey_tokey = match token {
+            Some(token) => token,
+            None => self.token().await?,
+        };
+
+        let mut headers = HashMap::new();
+        headers.insert("authorization".to_string(), tokey_tokey.authorization());
+        headers.insert("Content-Type".to_string(), "application/json".to_string());
+
+        Ok(headers)
+    }
+}
+
+/// A Character.AI client. The method groups are reached through [`AsyncClient::account`],
+/// [`AsyncClient::chat`] and so on.
+///
+/// Dropping the last handle to the client (and to its [`Requester`], if [`AsyncClient::get_requester`] was used)
+/// closes the websocket and releases the HTTP connection pool.
 pub struct AsyncClient {
-    token: RwLock<Option<String>>,
+    state: Arc<ClientState>,
     requester: Arc<Requester>,
 
-    pub data: RwLock<Account>,
-
-    pub account: RwLock<Option<AccountMethods>>,
-    pub user: RwLock<Option<UserMethods>>,
-    pub chat: RwLock<Option<ChatMethods>>,
-    pub character: RwLock<Option<CharacterMethods>>,
-    pub utils: RwLock<Option<UtilsMethods>>,
+    account: AccountMethods,
+    user: UserMethods,
+    chat: ChatMethods,
+    character: CharacterMethods,
+    utils: UtilsMethods,
 }
 
 impl AsyncClient {
     pub async fn new(token: Option<String>) -> Arc<Self> {
-        let requester = Arc::new(Requester::new());
-        
-        let arc = Arc::new(Self {
-            token: RwLock::new(token),
-            requester: requester.clone(),
-            
-            data: RwLock::new(Account::default()),
+        Self::with_config(token, RequesterConfig::default()).await
+    }
 
-            account: RwLock::new(None),
-            user: RwLock::new(None),
-            chat: RwLock::new(None),
-            character: RwLock::new(None),
-            utils: RwLock::new(None),
-        });
+    pub async fn with_config(token: Option<String>, config: RequesterConfig) -> Arc<Self> {
+        Self::with_requester(token, Requester::with_config(config)).await
+    }
 
-        let header_provider = arc.clone();
-
-        {
-            *arc.account.write().await = Some(AccountMethods::new(requester.clone(), header_provider.clone()));
-            *arc.user.write().await = Some(UserMethods::new(requester.clone(), header_provider.clone()));
-            *arc.chat.write().await = Some(ChatMethods::new(requester.clone(), header_provider.clone()));
-            *arc.character.write().await = Some(CharacterMethods::new(requester.clone(), header_provider.clone()));
-            *arc.utils.write().await = Some(UtilsMethods::new(requester.clone(), header_provider.clone()));
-            
-            let res = arc.account().await.fetch_profile().await;
-            if let Ok(account) = res {
-                arc.set_data(account).await;
-            }
+    /// A client using a ready-made requester, e.g. one built with [`Requester::with_transport`].
+    ///
+    /// The profile is fetched right away, but a failure is ignored and leaves [`AsyncClient::data`] empty; use
+    /// [`AsyncClient::builder`] to have it reported instead.
+    pub async fn with_requester(token: Option<String>, requester: Requester) -> Arc<Self> {
+        let client = Self::init(token.map(SecretToken::new), requester);
+
+        let res = client.account().fetch_profile().await;
+        if let Ok(account) = res {
+            client.set_data(account).await;
         }
 
-        arc
+        client
     }
 
-    pub async fn token(&self) -> String {
-        self.token.read().await.as_ref().unwrap().clone()
+    pub fn builder() -> AsyncClientBuilder {
+        AsyncClientBuilder::new()
     }
 
-    pub async fn data(&self) -> Account {
-        self.data.read().await.as_ref().clone()
+    fn init(token: Option<SecretToken>, requester: Requester) -> Arc<Self> {
+        let requester = Arc::new(requester);
+        let state = Arc::new(ClientState {
+            token: RwLock::new(token),
+            provider: requester.token_provider().cloned(),
+            data: RwLock::new(Account::default()),
+        });
+
+        Arc::new(Self {
+            account: AccountMethods::new(requester.clone(), state.clone()),
+            user: UserMethods::new(requester.clone(), state.clone()),
+            chat: ChatMethods::new(requester.clone(), state.clone()),
+            character: CharacterMethods::new(requester.clone(), state.clone()),
+            utils: UtilsMethods::new(requester.clone(), state.clone()),
+            state,
+            requester,
+        })
     }
 
-    pub async fn account(&self) -> Arc<AccountMethods> {
-        Arc::new(self.account.read().await.as_ref().unwrap().clone())
+    pub async fn token(&self) -> Result<SecretToken, RequesterError> {
+        self.state.token().await
     }
 
-    pub async fn user(&self) -> Arc<UserMethods> {
-        Arc::new(self.user.read().await.as_ref().unwrap().clone())
+    pub async fn data(&self) -> Account {
+        self.state.data().await
     }
 
-    pub async fn chat(&self) -> Arc<ChatMethods> {
-        Arc::new(self.chat.read().await.as_ref().unwrap().clone())
+    pub fn account(&self) -> &AccountMethods {
+        &self.account
     }
 
-    pub async fn character(&self) -> Arc<CharacterMethods> {
-        Arc::new(self.character.read().await.as_ref().unwrap().clone())
+    pub fn user(&self) -> &UserMethods {
+        &self.user
     }
 
-    pub async fn utils(&self) -> Arc<UtilsMethods> {
-        Arc::new(self.utils.read().await.as_ref().unwrap().clone())
+    pub fn chat(&self) -> &ChatMethods {
+        &self.chat
     }
 
-    pub async fn set_token(&self, token: impl Into<String>) {
-        *self.token.write().await = Some(token.into());
+    pub fn character(&self) -> &CharacterMethods {
+        &self.character
     }
 
-    pub async fn set_data(&self, account: impl Into<Account>) {
-        *self.data.write().await = account.into();
+    pub fn utils(&self) -> &UtilsMethods {
+        &self.utils
     }
 
-    pub async fn get_headers(&self, token: Option<String>) -> HashMap<String, String> {
-        let tokey_tokey: &String;
-        let read = self.token.read().await;
-        if token.is_none() {
-            if read.is_none() {
-                panic!("No token found.");
-            }
-            tokey_tokey = read.as_ref().unwrap();
-        } else {
-            tokey_tokey = token.as_ref().unwrap();
-        }
+    /// Replaces the token. It isn't used while the requester has a [`TokenProvider`].
+    pub async fn set_token(&self, token: impl Into<SecretToken>) {
+        *self.state.token.write().await = Some(token.into());
+    }
 
-        let mut headers = HashMap::new();
-        headers.insert("authorization".to_string(), format!("Token {}", tokey_tokey));
-        headers.insert("Content-Type".to_string(), "application/json".to_string());
+    pub async fn set_data(&self, account: impl Into<Account>) {
+        *self.state.data.write().await = account.into();
+    }
 
-        headers
+    pub(crate) async fn get_headers(&self, token: Option<SecretToken>) -> Result<HashMap<String, String>, RequesterError> {
+        self.state.get_headers(token).await
     }
 
     pub fn get_requester(&self) -> Arc<Requester> {
@@ -113,20 +153,218 @@ impl AsyncClient {
     pub async fn close_session(&self) {
         self.requester.ws_close().await;
     }
+
+    pub async fn session_health(&self) -> WsHealth {
+        self.requester.ws_health().await
+    }
+
+    /// Calls an endpoint this crate doesn't wrap yet, with the same auth headers, rate limiting and retries as
+    /// every other method. An empty response body comes back as `null`.
+    pub async fn raw_request(&self, method: impl Into<String>, host: Host, path: impl AsRef<str>, body: Option<Value>) -> Result<Value, RequesterError> {
+        let options = RequestOptions::new(method, self.get_headers(None).await?, body.map(|body| body.to_string().into()));
+        let res = self.requester.request_resp_async(self.requester.url(host, path), options).await?;
+        if res.body.is_empty() {
+            return Ok(Value::Null);
+        }
+
+        serde_json::from_slice(&res.body).map_err(|err| RequesterError::DecodeError { message: err.to_string(), payload: res.text() })
+    }
+
+    /// Sends a websocket command this crate doesn't wrap yet, streaming the events answering it.
+    pub async fn raw_ws_command(&self, command: &str, payload: Value) -> Result<impl Stream<Item = Result<WsEvent, RequesterError>> + use<>, RequesterError> {
+        self.chat().send_command(&WsCommand::Unknown(json!({ "command": command, "payload": payload }))).await
+    }
 }
 
-#[tokio::main]
-pub async fn main() {
-    let d = AsyncClient::new(Some("9912bd86846346c083cf48cd88f7300a114cf5c7".to_string())).await;
-    let da = d.account().await;
-    let account: Account = da.fetch_profile().await.expect("");
-    assert!(account == d.data().await);
-    println!("\x1b[1m\x1b[32mHEADERS\x1b[0m: {:?}", d.get_headers(None).await);
-    println!("\x1b[1m\x1b[32mPROFILE\x1b[0m: {:?}", account);
-    println!("\x1b[1m\x1b[32mSETTINGS\x1b[0m: {:?}", da.fetch_settings().await.expect(""));
-    println!("\x1b[1m\x1b[32mFOLLOWERS\x1b[0m: {:?}", da.fetch_followers().await.expect(""));
-    println!("\x1b[1m\x1b[32mFOLLOWING\x1b[0m: {:?}", da.fetch_following().await.expect(""));
-    println!("\x1b[1m\x1b[32mPERSONAS\x1b[0m: {:?}", da.fetch_personas().await.expect("")[0]);
-    println!("\x1b[1m\x1b[32mCHARS\x1b[0m: {:?}", da.fetch_characters_ranked().await.expect("")[0]);
-    println!("\x1b[1m\x1b[32mEDIT ACC SUCCEEDED\x1b[0m: {}", da.edit_account(&account.name, &account.username, Some(&account.bio), if let Some(avi) = &account.avatar { Some(&avi.file_name) } else { None }).await.expect(""));
-}
\ No newline at end of file
+/// Builds an [`AsyncClient`] from a token, a [`RequesterConfig`] and a few common overrides.
+///
+/// Unlike [`AsyncClient::new`], [`AsyncClientBuilder::build`] fails if the profile can't be fetched, so a bad token
+/// is reported at startup rather than as broken requests later on.
+#[derive(Debug)]
+pub struct AsyncClientBuilder {
+    token: Option<SecretToken>,
+    config: RequesterConfig,
+    transport: Option<Arc<dyn Transport>>,
+    token_provider: Option<Arc<dyn TokenProvider>>,
+    prefetch_profile: bool,
+}
+
+impl Default for AsyncClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AsyncClientBuilder {
+    pub fn new() -> Self {
+        Self { token: None, config: RequesterConfig::default(), transport: None, token_provider: None, prefetch_profile: true }
+    }
+
+    pub fn with_token(mut self, token: impl Into<SecretToken>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Replaces the whole config, including anything set by the other `with_*` functions before.
+    pub fn with_config(mut self, config: RequesterConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.config.request_timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.config.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.config.user_agent = user_agent.into();
+        self
+    }
+
+    /// Sends everything through `transport` instead of the network, e.g. a [`MemoryTransport`] in tests.
+    pub fn with_transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Sends everything through a transport shared with other clients, so they reuse one HTTP connection pool.
+    /// Each client still opens its own websocket.
+    pub fn with_shared_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Gets the token from `provider` rather than a fixed token, refreshing it when Character.AI rejects it.
+    pub fn with_token_provider(mut self, provider: impl TokenProvider + 'static) -> Self {
+        self.token_provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Whether [`AsyncClientBuilder::build`] fetches the profile. Defaults to `true`; without it,
+    /// [`AsyncClient::data`] stays empty until [`AsyncClient::set_data`] is called.
+    pub fn with_prefetch_profile(mut self, prefetch: bool) -> Self {
+        self.prefetch_profile = prefetch;
+        self
+    }
+
+    pub async fn build(self) -> Result<Arc<AsyncClient>, RequesterError> {
+        let transport = self.transport.unwrap_or_else(|| Arc::new(ReqwestTransport::from_config(&self.config)));
+        let mut requester = Requester::with_shared_transport(self.config, transport);
+        if let Some(provider) = self.token_provider {
+            requester = requester.with_token_provider(provider);
+        }
+        let client = AsyncClient::init(self.token, requester);
+
+        if self.prefetch_profile {
+            let account = client.account().fetch_profile().await?;
+            client.set_data(account).await;
+        }
+
+        Ok(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn builder_reports_a_bad_token() {
+        let transport = MemoryTransport::new();
+        transport.respond("GET", "/chat/user/", HttpResponse::new(401, ""));
+        let res = AsyncClient::builder().with_token("bad").with_transport(transport).build().await;
+        assert_eq!(res.err(), Some(RequesterError::AuthenticationError));
+
+        let transport = MemoryTransport::new();
+        transport.respond_json("GET", "/chat/user/", json!({ "user": { "user": { "id": 7, "username": "someone", "account": { "name": "Someone" } } } }));
+        let client = AsyncClient::builder()
+            .with_token("good")
+            .with_user_agent("rustic_ai-tests")
+            .with_transport(transport.clone())
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(client.data().await.id, 7);
+        assert_eq!(transport.requests()[0].headers["user-agent"], "rustic_ai-tests");
+
+        let client = AsyncClient::builder().with_prefetch_profile(false).build().await.unwrap();
+        assert_eq!(client.data().await, Account::default());
+    }
+
+    #[tokio::test]
+    async fn dropping_the_client_releases_the_requester() {
+        let transport = MemoryTransport::new();
+        transport.on_ws_command(|command| vec![json!({ "command": "ok", "request_id": command["request_id"] })]);
+        let client = AsyncClient::builder().with_token("token").with_prefetch_profile(false).with_transport(transport).build().await.unwrap();
+        assert!(client.chat().update_primary_candidate(&"chat".to_string(), &"turn".to_string(), &"candidate".to_string()).await);
+        assert!(client.session_health().await.connected);
+
+        let requester = Arc::downgrade(&client.get_requester());
+        let weak = Arc::downgrade(&client);
+        drop(client);
+        assert!(weak.upgrade().is_none());
+        assert!(requester.upgrade().is_none());
+    }
+
+    /// Hands out `stale` until asked to refresh it. Clones share the tokens they were asked to refresh.
+    #[derive(Debug, Default, Clone)]
+    struct Rotating {
+        refreshed: Arc<std::sync::Mutex<Vec<SecretToken>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl TokenProvider for Rotating {
+        async fn token(&self) -> Result<SecretToken, RequesterError> {
+            let refreshed = !self.refreshed.lock().unwrap().is_empty();
+            Ok(if refreshed { "fresh" } else { "stale" }.into())
+        }
+
+        async fn refresh(&self, rejected: &SecretToken) -> Result<SecretToken, RequesterError> {
+            self.refreshed.lock().unwrap().push(rejected.clone());
+            Ok("fresh".into())
+        }
+    }
+
+    /// Only lets the `fresh` token in.
+    #[derive(Debug)]
+    struct FreshOnly(MemoryTransport);
+
+    #[async_trait::async_trait]
+    impl Transport for FreshOnly {
+        async fn request(&self, request: HttpRequest) -> Result<HttpResponse, RequesterError> {
+            match request.headers["authorization"].as_str() {
+                "Token fresh" => self.0.request(request).await,
+                _ => Ok(HttpResponse::new(401, "")),
+            }
+        }
+
+        async fn connect_ws(&self, url: &str, headers: &HashMap<String, String>) -> Result<(WsSink, WsSource), RequesterError> {
+            match headers["Cookie"].contains("Token fresh") {
+                true => self.0.connect_ws(url, headers).await,
+                false => Err(RequesterError::AuthenticationError),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_tokens_are_refreshed_once() {
+        let transport = MemoryTransport::new();
+        transport.respond_json("GET", "/chat/user/", json!({ "user": { "user": { "id": 1, "username": "someone", "account": { "name": "Someone" } } } }));
+        let rotating = Rotating::default();
+        let client = AsyncClient::builder().with_token_provider(rotating.clone()).with_transport(FreshOnly(transport)).build().await.unwrap();
+        assert_eq!(client.data().await.username, "someone");
+        assert_eq!(client.token().await.unwrap(), "fresh".into());
+        assert_eq!(*rotating.refreshed.lock().unwrap(), [SecretToken::new("stale")]);
+
+        // The websocket is opened with the token from before the refresh, so it has to be refreshed again.
+        let rotating = Rotating::default();
+        let requester = Requester::with_transport(RequesterConfig::default(), FreshOnly(MemoryTransport::new())).with_token_provider(Arc::new(rotating.clone()));
+        assert!(requester.ws_send_and_receive(&json!({ "command": "x" }), "stale".into()).await.is_ok());
+        assert_eq!(*rotating.refreshed.lock().unwrap(), [SecretToken::new("stale")]);
+    }
+}
@@ -1,14 +1,58 @@
 pub mod client;
 pub mod methods;
 pub mod types;
-pub(crate) mod requester;
+pub mod requester;
+pub mod pool;
+pub mod paginator;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "mock-server")]
+pub mod mock;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "mock-server"))]
 mod tests {
     use super::*;
+    use mock::MockServer;
 
-    #[test]
-    fn it_works() {
-        client::main();
+    #[tokio::test]
+    async fn it_works() {
+        let server = MockServer::start().await.unwrap();
+        let client = client::AsyncClient::with_config(Some(MockServer::TOKEN.to_string()), server.config()).await;
+        assert_eq!(client.data().await.username, "mock_user");
+
+        let character_id = MockServer::CHARACTER_ID.to_string();
+        let character = client.character().fetch_character_info(&character_id).await.unwrap();
+        assert_eq!(character.name, "Mock");
+        assert_eq!(client.character().search_characters(&"mock".to_string()).await.unwrap().len(), 1);
+        assert!(client.utils().ping().await);
+
+        let chat = client.chat();
+        let (new_chat, greeting) = chat.create_chat(&character_id, true, None).await.unwrap();
+        assert_eq!(greeting.unwrap().get_primary_candidate().unwrap().text, "Hello! I'm a mock character.");
+
+        server.script(["Scripted hello"]);
+        let reply = chat.send_message(&character_id, &new_chat.id, &"Hi".to_string()).await.unwrap();
+        assert_eq!(reply.get_primary_candidate().unwrap().text, "Scripted hello");
+        let echo = chat.send_message(&character_id, &new_chat.id, &"Echo me".to_string()).await.unwrap();
+        assert_eq!(echo.get_primary_candidate().unwrap().text, "Echo me");
+
+        let retried = chat.retry_response(&character_id, &new_chat.id, &echo.id).await.unwrap();
+        assert_eq!(retried.candidates.len(), 2);
+        let candidate_id = retried.get_primary_candidate().unwrap().id.clone();
+        let edited = chat.edit_message(&new_chat.id, &echo.id, &candidate_id, &"Edited".to_string()).await.unwrap();
+        assert_eq!(edited.get_primary_candidate().unwrap().text, "Edited");
+        assert!(edited.get_primary_candidate().unwrap().is_edited());
+        assert!(chat.set_turn_pin(&new_chat.id, &echo.id, true).await);
+        let (pinned, _) = chat.fetch_messages(&new_chat.id, true, None).await.unwrap();
+        assert_eq!(pinned.iter().map(|t| &t.id).collect::<Vec<_>>(), [&echo.id]);
+        assert!(chat.update_primary_candidate(&new_chat.id, &echo.id, &candidate_id).await);
+
+        assert!(chat.delete_messages(&new_chat.id, vec![&echo.id]).await);
+        assert_eq!(chat.fetch_chats(&character_id, 0).await.unwrap().len(), 1);
+        let (turns, _) = chat.fetch_messages(&new_chat.id, false, None).await.unwrap();
+        assert_eq!(turns.len(), 4);
+
+        let stranger = client::AsyncClient::with_config(Some("wrong-token".to_string()), server.config()).await;
+        assert!(stranger.account().fetch_profile().await.is_err());
     }
 }
@@ -1,129 +1,189 @@
-use std::{sync::Arc, collections::HashMap};
+use std::{sync::Arc, collections::HashMap, future::Future};
 use tokio::pin;
-use futures_util::{Stream, StreamExt};
+use futures_util::{Stream, StreamExt, TryStreamExt};
 use async_stream::stream;
 use rand::Rng;
 use base64::{Engine, engine::general_purpose};
 use serde_json::{json, Value};
 use uuid::Uuid;
 
-use crate::{client::AsyncClient, requester::*, types::{character::*, chat::*, enums::Visibility, media::*, user::*}};
+use crate::{client::ClientState, paginator::{Page, Paginator}, requester::*, types::{character::*, chat::*, enums::Visibility, ids::*, media::*, parse::ParseError, user::*, ws::*}};
+
+fn parse<T>(json: &Value) -> Result<T, RequesterError> where T: for<'a> TryFrom<&'a Value, Error = ParseError> {
+    T::try_from(json).map_err(|err| RequesterError::decode(err.to_string(), json))
+}
+
+fn parse_list<T>(json: &Value, field: &str) -> Result<Vec<T>, RequesterError> where T: for<'a> TryFrom<&'a Value, Error = ParseError> {
+    json.get(field).and_then(|v| v.as_array()).map_or(Ok(vec![]), |items| items.iter().map(parse).collect())
+}
+
+/// A paginator calling `fetch_page` on its own clone of a method group for every page.
+fn paginate<M, T, F, Fut>(methods: &M, fetch_page: F) -> Paginator<T>
+where
+    M: Clone + Send + Sync + 'static,
+    F: Fn(M, Option<String>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Page<T>, RequesterError>> + Send + 'static,
+{
+    let methods = methods.clone();
+    Paginator::new(move |cursor| fetch_page(methods.clone(), cursor))
+}
+
+/// Adds the cursor of a page to `url` as the `param` query parameter.
+fn with_cursor(url: String, param: &str, cursor: Option<&str>) -> String {
+    match cursor {
+        Some(cursor) => format!("{}{}{}={}", url, if url.contains('?') { '&' } else { '?' }, param, urlencoding::encode(cursor)),
+        None => url,
+    }
+}
+
+/// The cursor of the next page at `pointer`, if the response has one.
+fn next_cursor(json: &Value, pointer: &str) -> Option<String> {
+    json.pointer(pointer).and_then(|v| v.as_str()).filter(|s| !s.is_empty()).map(String::from)
+}
+
+fn validate_length(field: &str, value: &str, min: usize, max: usize) -> Result<(), RequesterError> {
+    if value.len() > max && min == 0 {
+        Err(RequesterError::ValidationError(format!("{} cannot be more than {} characters (is {} characters)", field, max, value.len())))
+    } else if value.len() < min || value.len() > max {
+        Err(RequesterError::ValidationError(format!("{} cannot be less than {} characters or more than {} (is {} characters)", field, min, max, value.len())))
+    } else {
+        Ok(())
+    }
+}
 
 #[derive(Clone)]
 pub struct AccountMethods {
     requester: Arc<Requester>,
-    client: Arc<AsyncClient>
+    client: Arc<ClientState>
 }
 
 impl AccountMethods {
-    pub fn new(requester: Arc<Requester>, client: Arc<AsyncClient>) -> Self {
+    pub(crate) fn new(requester: Arc<Requester>, client: Arc<ClientState>) -> Self {
         Self { requester, client }
     }
 
     pub async fn fetch_profile(&self) -> Result<Account, RequesterError> {
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/user/",
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
+            self.requester.url(Host::Plus, "/chat/user/"),
+            RequestOptions::new("GET", self.client.get_headers(None).await?, None)
         ).await?;
 
-        const ERR: &str = "Something went wrong with the Character.AI API; fetch_profile response was missing user object";
-        Ok(Account::from_json(json.get("user").expect(ERR).get("user").expect(ERR)))
+        match json.get("user").and_then(|u| u.get("user")) {
+            Some(user) => parse(user),
+            None => Err(RequesterError::decode("fetch_profile response was missing user object", &json))
+        }
     }
 
     pub async fn fetch_settings(&self) -> Result<Settings, RequesterError> {
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/user/settings/",
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
+            self.requester.url(Host::Plus, "/chat/user/settings/"),
+            RequestOptions::new("GET", self.client.get_headers(None).await?, None)
         ).await?;
 
-        Ok(Settings::from_json(&json))
+        parse(&json)
     }
 
     pub async fn fetch_followers(&self) -> Result<Vec<String>, RequesterError> {
-        let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/user/followers/",
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
-        ).await?;
-        
-        Ok(json.get("followers").unwrap_or(&json!([])).as_array().unwrap().into_iter().filter_map(|v| v.as_str().map(String::from)).collect())
+        self.fetch_followers_stream().try_collect().await
+    }
+
+    pub fn fetch_followers_stream(&self) -> Paginator<String> {
+        paginate(self, |methods, page| async move { methods.fetch_follow_page("followers", page).await })
     }
 
     pub async fn fetch_following(&self) -> Result<Vec<String>, RequesterError> {
+        self.fetch_following_stream().try_collect().await
+    }
+
+    pub fn fetch_following_stream(&self) -> Paginator<String> {
+        paginate(self, |methods, page| async move { methods.fetch_follow_page("following", page).await })
+    }
+
+    /// A page of followers or followed users. The cursor is the page number, starting from 1.
+    async fn fetch_follow_page(&self, field: &str, page: Option<String>) -> Result<Page<String>, RequesterError> {
+        let page = page.and_then(|p| p.parse::<u32>().ok()).unwrap_or(1);
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/user/following/",
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
+            self.requester.url(Host::Plus, format!("/chat/user/{}/?page={}", field, page)),
+            RequestOptions::new("GET", self.client.get_headers(None).await?, None)
         ).await?;
-        
-        Ok(json.get("following").unwrap_or(&json!([])).as_array().unwrap().into_iter().filter_map(|v| v.as_str().map(String::from)).collect())
+
+        let users = json.get(field).unwrap_or(&json!([])).as_array().unwrap_or(&vec![]).iter().filter_map(|v| v.as_str().map(String::from)).collect();
+        let has_next_page = json.get("has_next_page").and_then(|v| v.as_bool()).unwrap_or(false);
+        Ok(Page::new(users, has_next_page.then(|| (page + 1).to_string())))
     }
 
-    pub async fn fetch_persona(&self, id: impl Into<&String>) -> Result<Persona, RequesterError> {
+    pub async fn fetch_persona(&self, id: impl AsRef<PersonaIdRef>) -> Result<Persona, RequesterError> {
         let json: Value = self.requester.request_async(
-            format!("https://plus.character.ai/chat/persona/?id={}", id.into()),
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
+            self.requester.url(Host::Plus, format!("/chat/persona/?id={}", id.as_ref())),
+            RequestOptions::new("GET", self.client.get_headers(None).await?, None)
         ).await?;
         
-        Ok(Persona::from_json(&json))
+        parse(&json)
     }
 
     pub async fn fetch_personas(&self) -> Result<Vec<Persona>, RequesterError> {
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/personas/?force_refresh=1",
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
+            self.requester.url(Host::Plus, "/chat/personas/?force_refresh=1"),
+            RequestOptions::new("GET", self.client.get_headers(None).await?, None)
         ).await?;
         
-        Ok(json.get("personas").unwrap_or(&json!([])).as_array().unwrap().into_iter().filter_map(|v| Some(Persona::from_json(&v))).collect())
+        parse_list(&json, "personas")
     }
 
     pub async fn fetch_characters(&self) -> Result<Vec<PartialCharacter>, RequesterError> {
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/chracters/?scope=user",
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
+            self.requester.url(Host::Plus, "/chat/chracters/?scope=user"),
+            RequestOptions::new("GET", self.client.get_headers(None).await?, None)
         ).await?;
 
-        Ok(json.get("characters").unwrap_or(&json!([])).as_array().unwrap().into_iter().filter_map(|v| Some(PartialCharacter::from_json(&v))).collect())
+        parse_list(&json, "characters")
     }
 
     pub async fn fetch_characters_ranked(&self) -> Result<Vec<PartialCharacter>, RequesterError> {
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/user/characters/upvoted/",
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
+            self.requester.url(Host::Plus, "/chat/user/characters/upvoted/"),
+            RequestOptions::new("GET", self.client.get_headers(None).await?, None)
         ).await?;
         
-        Ok(json.get("characters").unwrap_or(&json!([])).as_array().unwrap().into_iter().filter_map(|v| Some(PartialCharacter::from_json(&v))).collect())
+        parse_list(&json, "characters")
     }
 
     pub async fn fetch_voices(&self) -> Result<Vec<Voice>, RequesterError> {
-        let json: Value = self.requester.request_async(
-            "https://plus.character.ai/multimodal/api/v1/voices/user",
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
-        ).await?;
-        
-        Ok(json.get("voices").unwrap_or(&json!([])).as_array().unwrap().into_iter().filter_map(|v| Some(Voice::from_json(&v))).collect())
+        self.fetch_voices_stream().try_collect().await
+    }
+
+    pub fn fetch_voices_stream(&self) -> Paginator<Voice> {
+        paginate(self, |methods, cursor| async move {
+            let json: Value = methods.requester.request_async(
+                with_cursor(methods.requester.url(Host::Plus, "/multimodal/api/v1/voices/user"), "pageToken", cursor.as_deref()),
+                RequestOptions::new("GET", methods.client.get_headers(None).await?, None)
+            ).await?;
+
+            Ok(Page::new(parse_list(&json, "voices")?, next_cursor(&json, "/nextPageToken")))
+        })
     }
 
-    async fn update_settings(&self, default_persona_id: Option<&String>, persona_override: Option<&String>, voice_override: Option<&String>, character_id: Option<&String>, settings: Option<&mut Settings>) -> Result<Settings, RequesterError> {
+    async fn update_settings(&self, default_persona_id: Option<&PersonaIdRef>, persona_override: Option<&PersonaIdRef>, voice_override: Option<&VoiceIdRef>, character_id: Option<&CharacterIdRef>, settings: Option<&mut Settings>) -> Result<Settings, RequesterError> {
         if default_persona_id.is_none() && persona_override.is_none() && voice_override.is_none() {
-            panic!("You must provide an updated value when calling update_settings");
+            return Err(RequesterError::ValidationError("you must provide an updated value when calling update_settings".to_string()));
         }
         let settings: &mut Settings = if let Some(real) = settings { real } else { &mut self.fetch_settings().await? };
         if let Some(dpi) = default_persona_id {
-            settings.default_persona_id = dpi.to_string();
+            settings.default_persona_id = dpi.to_owned();
         }
-        if let Some(ci) = character_id {
-            if let Some(po) = persona_override {
-                settings.persona_overrides.insert(ci.to_string(), po.to_string());
+        if let Some(ci) = character_id
+            && let Some(po) = persona_override {
+                settings.persona_overrides.insert(ci.to_owned(), po.to_owned());
             }
-        }
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/user/update_settings/",
-            RequestOptions::new("POST", self.client.get_headers(None).await, Some(settings.to_json().to_string().into()))
+            self.requester.url(Host::Plus, "/chat/user/update_settings/"),
+            RequestOptions::new("POST", self.client.get_headers(None).await?, Some(settings.to_json().to_string().into()))
         ).await?;
 
-        if json.get("success").unwrap_or(&json!(false)).as_bool().unwrap() {
-            Err(RequesterError::RequestFailed(format!("cargo:warn=Malformed data received.\n{:?}", json)))
+        if !json.get("success").and_then(|v| v.as_bool()).unwrap_or(false) {
+            Err(RequesterError::ApiError(format!("cannot update settings: {}", json.get("error").and_then(|v| v.as_str()).unwrap_or(""))))
         } else {
-            Ok(Settings::from_json(&json))
+            parse(&json)
         }
     }
 
@@ -133,17 +193,11 @@ impl AccountMethods {
         let bio: &String = if let Some(v) = bio { v } else { &"".to_string() };
         let avatar_path: &String = if let Some(v) = avatar_path { v } else { &"".to_string() };
 
-        if username.len() < 2 || username.len() > 20 {
-            panic!("username cannot be less than 2 characters or more than 20 (is {} characters)", username.len());
-        }
-        if name.len() < 2 || name.len() > 50 {
-            panic!("name cannot be less than 2 characters or more than 50 (is {} characters)", name.len());
-        }
-        if bio.len() > 500 {
-            panic!("bio cannot be more than 500 characters (is {} characters)", bio.len());
-        }
+        validate_length("username", username, 2, 20)?;
+        validate_length("name", name, 2, 50)?;
+        validate_length("bio", bio, 0, 500)?;
 
-        let path = avatar_path.len() > 0;
+        let path = !avatar_path.is_empty();
         let mut inf = json!({
             "name": name,
             "username": username,
@@ -155,8 +209,8 @@ impl AccountMethods {
         }
 
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/user/update/",
-            RequestOptions::new("POST", self.client.get_headers(None).await, Some(inf.to_string().into()))
+            self.requester.url(Host::Plus, "/chat/user/update/"),
+            RequestOptions::new("POST", self.client.get_headers(None).await?, Some(inf.to_string().into()))
         ).await?;
 
         Ok(json.get("status").unwrap_or(&json!("")).as_str().unwrap_or("") != "OK")
@@ -164,19 +218,15 @@ impl AccountMethods {
 
     pub async fn create_persona(&self, name: impl Into<&String>, definition: Option<String>, avatar_path: Option<String>) -> Result<Persona, RequesterError> {
         let name: &String = name.into();
-        let definition: String = definition.unwrap_or_else(|| "".to_string());
-        let avatar_path: String = avatar_path.unwrap_or_else(|| "".to_string());
+        let definition: String = definition.unwrap_or_default();
+        let avatar_path: String = avatar_path.unwrap_or_default();
 
-        if name.len() < 3 || name.len() > 20 {
-            panic!("name cannot be less than 3 characters or more than 20 (is {} characters)", name.len());
-        }
-        if definition.len() > 720 {
-            panic!("definition cannot be more than 720 characters (is {} characters)", definition.len());
-        }
+        validate_length("name", name, 3, 20)?;
+        validate_length("definition", &definition, 0, 720)?;
 
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/persona/create/",
-            RequestOptions::new("POST", self.client.get_headers(None).await, Some(json!({
+            self.requester.url(Host::Plus, "/chat/persona/create/"),
+            RequestOptions::new("POST", self.client.get_headers(None).await?, Some(json!({
                 "avatar_file_name": "",
                 "avatar_rel_path": avatar_path,
                 "base_img_prompt": "",
@@ -196,27 +246,29 @@ impl AccountMethods {
         ).await?;
 
         if json.get("status").unwrap_or(&json!("")).as_str().unwrap_or("") != "OK" || json.get("persona").unwrap_or(&Value::Null) == &Value::Null {
-            Err(RequesterError::RequestFailed(format!("{}", json.get("error").unwrap_or(&json!("")))))
+            Err(RequesterError::ApiError(json.get("error").and_then(|v| v.as_str()).unwrap_or("").to_string()))
         } else {
-            Ok(Persona::from_json(&json.get("persona").unwrap_or(&json!({}))))
+            parse(json.get("persona").unwrap_or(&json!({})))
         }
     }
 
     async fn update_persona_internal(&self, json: Value) -> Result<Persona, RequesterError> {
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/persona/update/",
-            RequestOptions::new("POST", self.client.get_headers(None).await, Some(json.to_string().into()))
+            self.requester.url(Host::Plus, "/chat/persona/update/"),
+            RequestOptions::new("POST", self.client.get_headers(None).await?, Some(json.to_string().into()))
         ).await?;
 
         if json.get("status").unwrap_or(&json!("")).as_str().unwrap_or("") != "OK" || json.get("persona").unwrap_or(&Value::Null) == &Value::Null {
-            Err(RequesterError::RequestFailed(format!("{}", json.get("error").unwrap_or(&json!("")))))
+            Err(RequesterError::ApiError(json.get("error").and_then(|v| v.as_str()).unwrap_or("").to_string()))
         } else {
-            Ok(Persona::from_json(&json.get("persona").unwrap_or(&json!({}))))
+            parse(json.get("persona").unwrap_or(&json!({})))
         }
     }
 
-    pub async fn edit_persona(&self, id: impl Into<&String>, name: Option<&String>, definition: Option<&String>, avatar_path: Option<&String>, persona: Option<&Persona>) -> Result<Persona, RequesterError> {
-        let id: &String = id.into();
+    /// Updates a persona. A missing or invalid `name` (3 to 20 characters) falls back to the name of `persona`,
+    /// and fails validation if that isn't valid either.
+    pub async fn edit_persona(&self, id: impl AsRef<PersonaIdRef>, name: Option<&String>, definition: Option<&String>, avatar_path: Option<&String>, persona: Option<&Persona>) -> Result<Persona, RequesterError> {
+        let id = id.as_ref();
         let persona: &Persona = if let Some(v) = persona { v } else { &Persona::default() };
         let account: Account = self.client.data().await;
 
@@ -225,16 +277,11 @@ impl AccountMethods {
         let avatar_path: &String = if let Some(v) = avatar_path { v } else { &"".to_string() };
 
         if name.len() < 3 || name.len() > 20 {
-            println!("cargo:warning=name cannot be less than 3 characters or more than 20 characters (is {} characters). Falling back to provided persona name", name.len());
             name = &persona.name;
-            if name.len() < 3 || name.len() > 20 {
-                // There wasn't a provided persona, causing the persona variable to be the default persona, and thus have an invalid name, or the provided persona already had an invalid name somehow.
-                panic!("name cannot be less than 3 characters or more than 20 characters (is {} characters). Please provide a Persona object to `edit_persona` for it to fall back to if you plan to trigger this data validation error.", name.len());
-            }
-        }
-        if definition.len() > 720 {
-            panic!("definition cannot be more than 720 characters (is {} characters)", definition.len());
+            // Fails if there wasn't a provided persona, causing the persona variable to be the default persona, and thus have an invalid name, or the provided persona already had an invalid name somehow.
+            validate_length("name", name, 3, 20)?;
         }
+        validate_length("definition", definition, 0, 720)?;
 
         self.update_persona_internal(json!({
             "avatar_file_name": avatar_path,
@@ -243,7 +290,7 @@ impl AccountMethods {
             "is_persona": true,
             "copyable": false,
             "definition": definition,
-            "external_id": id,
+            "external_id": id.as_str(),
             "description": persona.description,
             "greeting": persona.greeting,
             "enabled": false,
@@ -259,8 +306,8 @@ impl AccountMethods {
         })).await
     }
 
-    pub async fn delete_persona(&self, id: impl Into<&String>, persona: Option<&Persona>) -> Result<Persona, RequesterError> {
-        let id: &String = id.into();
+    pub async fn delete_persona(&self, id: impl AsRef<PersonaIdRef>, persona: Option<&Persona>) -> Result<Persona, RequesterError> {
+        let id = id.as_ref();
         let persona: &Persona = if let Some(v) = persona { v } else { &self.fetch_persona(id).await? };
         let p: String = if let Some(v) = persona.avatar.clone() { v.file_name } else { "".to_string() };
         let account: Account = self.client.data().await;
@@ -273,7 +320,7 @@ impl AccountMethods {
             "is_persona": true,
             "copyable": false,
             "definition": persona.definition,
-            "external_id": id,
+            "external_id": id.as_str(),
             "description": persona.description,
             "greeting": persona.greeting,
             "enabled": false,
@@ -289,319 +336,306 @@ impl AccountMethods {
         })).await
     }
 
-    pub async fn set_default_persona(&self, id: Option<&String>, settings: Option<&mut Settings>) -> bool {
-        let id: &String = if let Some(v) = id { v } else { &"".to_string() };
-        self.update_settings(Some(id), None, None, None, settings).await.map_or(false, |_| true)
+    pub async fn set_default_persona(&self, id: Option<&PersonaIdRef>, settings: Option<&mut Settings>) -> bool {
+        let id = id.unwrap_or(PersonaIdRef::new(""));
+        self.update_settings(Some(id), None, None, None, settings).await.is_ok()
     }
 
-    pub async fn set_persona(&self, character_id: impl Into<&String>, persona_id: Option<&String>, settings: Option<&mut Settings>) -> bool {
-        let persona_id: &String = if let Some(v) = persona_id { v } else { &"".to_string() };
-        self.update_settings(None, Some(persona_id), None, Some(character_id.into()), settings).await.map_or(false, |_| true)
+    pub async fn set_persona(&self, character_id: impl AsRef<CharacterIdRef>, persona_id: Option<&PersonaIdRef>, settings: Option<&mut Settings>) -> bool {
+        let persona_id = persona_id.unwrap_or(PersonaIdRef::new(""));
+        self.update_settings(None, Some(persona_id), None, Some(character_id.as_ref()), settings).await.is_ok()
     }
 
-    pub async fn set_voice(&self, voice_id: Option<&String>, settings: Option<&mut Settings>) -> bool {
-        let voice_id: &String = if let Some(v) = voice_id { v } else { &"".to_string() };
-        self.update_settings(None, None, Some(voice_id), None, settings).await.map_or(false, |_| true)
+    pub async fn set_voice(&self, voice_id: Option<&VoiceIdRef>, settings: Option<&mut Settings>) -> bool {
+        let voice_id = voice_id.unwrap_or(VoiceIdRef::new(""));
+        self.update_settings(None, None, Some(voice_id), None, settings).await.is_ok()
     }
 }
 
 #[derive(Clone)]
 pub struct UserMethods {
     requester: Arc<Requester>,
-    client: Arc<AsyncClient>
+    client: Arc<ClientState>
 }
 
 impl UserMethods {
-    pub fn new(requester: Arc<Requester>, client: Arc<AsyncClient>) -> Self {
+    pub(crate) fn new(requester: Arc<Requester>, client: Arc<ClientState>) -> Self {
         Self { requester, client }
     }
 
     pub async fn fetch_user(&self, username: impl Into<&String>) -> Result<User, RequesterError> {
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/user/public/",
-            RequestOptions::new("POST", self.client.get_headers(None).await, Some(json!({ "username": username.into() }).to_string().into()))
+            self.requester.url(Host::Plus, "/chat/user/public/"),
+            RequestOptions::new("POST", self.client.get_headers(None).await?, Some(json!({ "username": username.into() }).to_string().into()))
         ).await?;
     
-        Ok(User::from_json(json.get("public_user").unwrap_or(&json!({}))))
+        parse(json.get("public_user").unwrap_or(&json!({})))
     }
     
     pub async fn fetch_user_voices(&self, username: impl Into<&String>) -> Result<Vec<Voice>, RequesterError> {
-        let json: Value = self.requester.request_async(
-            format!("https://neo.character.ai/multimodal/api/v1/voices/search?creatorInfo.username={}", username.into()),
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
-        ).await?;
-        
-        Ok(json.get("voices").and_then(|voices| voices.as_array()).unwrap_or(&vec![]).iter().map(|v| Voice::from_json(v)).collect())
+        self.fetch_user_voices_stream(username).try_collect().await
+    }
+
+    pub fn fetch_user_voices_stream<'s>(&self, username: impl Into<&'s String>) -> Paginator<Voice> {
+        let url = self.requester.url(Host::Neo, format!("/multimodal/api/v1/voices/search?creatorInfo.username={}", urlencoding::encode(username.into())));
+        paginate(self, move |methods, cursor| {
+            let url = with_cursor(url.clone(), "pageToken", cursor.as_deref());
+            async move {
+                let json: Value = methods.requester.request_async(url, RequestOptions::new("GET", methods.client.get_headers(None).await?, None)).await?;
+                Ok(Page::new(parse_list(&json, "voices")?, next_cursor(&json, "/nextPageToken")))
+            }
+        })
     }
     
     pub async fn follow_user(&self, username: impl Into<&String>) -> bool {
+        let Ok(headers) = self.client.get_headers(None).await else { return false };
         let resp = self.requester.request_async(
-            "https://plus.character.ai/chat/user/follow/",
-            RequestOptions::new("POST", self.client.get_headers(None).await, Some(json!({ "username": username.into() }).to_string().into()))
+            self.requester.url(Host::Plus, "/chat/user/follow/"),
+            RequestOptions::new("POST", headers, Some(json!({ "username": username.into() }).to_string().into()))
         ).await;
     
-        resp.map_or(false, |v| v.get("status").unwrap_or(&json!("")).as_str().unwrap_or("") == "OK")
+        resp.is_ok_and(|v| v.get("status").unwrap_or(&json!("")).as_str().unwrap_or("") == "OK")
     }
     
-    pub async fn unfollow_user(&self, username: impl Into<&String>, token: Option<String>) -> bool {
+    pub async fn unfollow_user(&self, username: impl Into<&String>, token: Option<SecretToken>) -> bool {
+        let Ok(headers) = self.client.get_headers(token).await else { return false };
         let resp = self.requester.request_async(
-            "https://plus.character.ai/chat/user/unfollow/",
-            RequestOptions::new("POST", self.client.get_headers(token).await, Some(json!({ "username": username.into() }).to_string().into()))
+            self.requester.url(Host::Plus, "/chat/user/unfollow/"),
+            RequestOptions::new("POST", headers, Some(json!({ "username": username.into() }).to_string().into()))
         ).await;
     
-        resp.map_or(false, |v| v.get("status").unwrap_or(&json!("")).as_str().unwrap_or("") == "OK")
+        resp.is_ok_and(|v| v.get("status").unwrap_or(&json!("")).as_str().unwrap_or("") == "OK")
     }    
 }
 
 #[derive(Clone)]
 pub struct ChatMethods {
     requester: Arc<Requester>,
-    client: Arc<AsyncClient>
+    client: Arc<ClientState>
 }
 
 impl ChatMethods {
-    pub fn new(requester: Arc<Requester>, client: Arc<AsyncClient>) -> Self {
+    pub(crate) fn new(requester: Arc<Requester>, client: Arc<ClientState>) -> Self {
         Self { requester, client }
     }
 
-    pub async fn fetch_histories(&self, character_id: impl Into<&String>, amount: usize) -> Result<Vec<ChatHistory>, RequesterError> {
+    pub async fn fetch_histories(&self, character_id: impl AsRef<CharacterIdRef>, amount: usize) -> Result<Vec<ChatHistory>, RequesterError> {
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/character/histories/",
-            RequestOptions::new("POST", self.client.get_headers(None).await, Some(json!({
-                "external_id": character_id.into(),
+            self.requester.url(Host::Plus, "/chat/character/histories/"),
+            RequestOptions::new("POST", self.client.get_headers(None).await?, Some(json!({
+                "external_id": character_id.as_ref().as_str(),
                 "number": amount
             }).to_string().into()))
         ).await?;
     
-        Ok(json.get("histories").unwrap_or(&json!([])).as_array().unwrap_or(&vec![]).iter().map(|h| ChatHistory::from_json(h)).collect())
+        parse_list(&json, "histories")
     }
 
-    pub async fn fetch_chats(&self, character_id: impl Into<&String>, num_preview_turns: usize) -> Result<Vec<Chat>, RequesterError> {
-        let json: Value = self.requester.request_async(
-            format!("https://neo.character.ai/chats/?character_ids={}&num_preview_turns={}", character_id.into(), num_preview_turns),
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
-        ).await?;
-    
-        Ok(json.get("chats").unwrap_or(&json!([])).as_array().unwrap_or(&vec![]).iter().map(|c| Chat::from_json(c)).collect())
+    pub async fn fetch_chats(&self, character_id: impl AsRef<CharacterIdRef>, num_preview_turns: usize) -> Result<Vec<Chat>, RequesterError> {
+        self.fetch_chats_stream(character_id, num_preview_turns).try_collect().await
     }
 
-    pub async fn fetch_chat(&self, chat_id: impl Into<&String>) -> Result<Chat, RequesterError> {
+    pub fn fetch_chats_stream(&self, character_id: impl AsRef<CharacterIdRef>, num_preview_turns: usize) -> Paginator<Chat> {
+        let url = self.requester.url(Host::Neo, format!("/chats/?character_ids={}&num_preview_turns={}", character_id.as_ref(), num_preview_turns));
+        paginate(self, move |methods, cursor| methods.fetch_chats_page(url.clone(), cursor))
+    }
+
+    pub async fn fetch_chat(&self, chat_id: impl AsRef<ChatIdRef>) -> Result<Chat, RequesterError> {
         let json: Value = self.requester.request_async(
-            format!("https://neo.character.ai/chat/{}/", chat_id.into()),
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
+            self.requester.url(Host::Neo, format!("/chat/{}/", chat_id.as_ref())),
+            RequestOptions::new("GET", self.client.get_headers(None).await?, None)
         ).await?;
     
-        Ok(Chat::from_json(json.get("chat").unwrap_or(&json!({}))))
+        parse(json.get("chat").unwrap_or(&json!({})))
     }
     
     pub async fn fetch_recent_chats(&self) -> Result<Vec<Chat>, RequesterError> {
+        self.fetch_recent_chats_stream().try_collect().await
+    }
+
+    pub fn fetch_recent_chats_stream(&self) -> Paginator<Chat> {
+        let url = self.requester.url(Host::Neo, "/chats/recent/");
+        paginate(self, move |methods, cursor| methods.fetch_chats_page(url.clone(), cursor))
+    }
+
+    async fn fetch_chats_page(self, url: String, next_token: Option<String>) -> Result<Page<Chat>, RequesterError> {
         let json: Value = self.requester.request_async(
-            "https://neo.character.ai/chats/recent/",
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
+            with_cursor(url, "next_token", next_token.as_deref()),
+            RequestOptions::new("GET", self.client.get_headers(None).await?, None)
         ).await?;
-    
-        Ok(json.get("chats").unwrap_or(&json!([])).as_array().unwrap_or(&vec![]).iter().map(|c| Chat::from_json(c)).collect())
+
+        Ok(Page::new(parse_list(&json, "chats")?, next_cursor(&json, "/meta/next_token")))
     }
 
-    pub async fn fetch_messages(&self, chat_id: impl Into<&String>, pinned_only: bool, next_token: Option<String>) -> Result<(Vec<Turn>, Option<String>), RequesterError> {
-        let mut url = format!("https://neo.character.ai/turns/{}/", chat_id.into());
-    
-        if let Some(token) = &next_token {
-            url = format!("{}?next_token={}", url, urlencoding::encode(token));
+    /// A single page of turns, newest first, and the token of the page after it.
+    pub async fn fetch_messages(&self, chat_id: impl AsRef<ChatIdRef>, pinned_only: bool, next_token: Option<String>) -> Result<(Vec<Turn>, Option<String>), RequesterError> {
+        let Page { mut items, next_cursor } = self.fetch_turns_page(chat_id.as_ref(), next_token).await?;
+        if pinned_only {
+            items.retain(|turn| turn.is_pinned);
         }
-    
+
+        Ok((items, next_cursor))
+    }
+
+    /// Streams the turns of a chat, newest first. Pages without pinned turns are skipped when `pinned_only` is set.
+    pub fn fetch_messages_stream(&self, chat_id: impl AsRef<ChatIdRef>, pinned_only: bool) -> Paginator<Turn> {
+        let chat_id = chat_id.as_ref().to_owned();
+        paginate(self, move |methods, mut next_token| {
+            let chat_id = chat_id.clone();
+            async move {
+                loop {
+                    let mut page = methods.fetch_turns_page(&chat_id, next_token).await?;
+                    if pinned_only && !page.items.is_empty() {
+                        page.items.retain(|turn| turn.is_pinned);
+                        if page.items.is_empty() && page.next_cursor.is_some() {
+                            next_token = page.next_cursor;
+                            continue;
+                        }
+                    }
+                    return Ok(page);
+                }
+            }
+        })
+    }
+
+    async fn fetch_turns_page(&self, chat_id: &ChatIdRef, next_token: Option<String>) -> Result<Page<Turn>, RequesterError> {
         let json: Value = self.requester.request_async(
-            url,
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
+            with_cursor(self.requester.url(Host::Neo, format!("/turns/{}/", chat_id)), "next_token", next_token.as_deref()),
+            RequestOptions::new("GET", self.client.get_headers(None).await?, None)
         ).await?;
-        
-        let next_token = json.get("meta")
-            .and_then(|meta| meta.get("next_token"))
-            .and_then(|t| t.as_str())
-            .map(|s| s.to_string());
-
-        let turns = json.get("turns")
-            .unwrap_or(&json!([]))
-            .as_array()
-            .unwrap_or(&vec![])
-            .iter()
-            .filter(|t| {
-                if !pinned_only {
-                    true
-                } else {
-                    t.get("is_pinned").and_then(|p| p.as_bool()).unwrap_or(false)
-                }
-            })
-            .map(|t| Turn::from_json(t))
-            .collect();
-    
-        Ok((turns, next_token))
+
+        Ok(Page::new(parse_list(&json, "turns")?, next_cursor(&json, "/meta/next_token")))
     }
-    
-    pub async fn fetch_all_messages(&self, chat_id: impl Into<&String>, pinned_only: bool) -> Result<Vec<Turn>, RequesterError> {
-        let chat_id = chat_id.into();
-        let mut all_turns = Vec::new();
-        let mut next_token = None;
-    
-        loop {
-            let (turns, token) = self.fetch_messages(chat_id, pinned_only, next_token).await?;
-            if turns.is_empty() {
-                break;
-            }
-            all_turns.extend(turns);
-            if token.is_none() {
-                break;
-            }
-            next_token = token;
-        }
-    
-        Ok(all_turns)
+
+    pub async fn fetch_all_messages(&self, chat_id: impl AsRef<ChatIdRef>, pinned_only: bool) -> Result<Vec<Turn>, RequesterError> {
+        self.fetch_messages_stream(chat_id, pinned_only).try_collect().await
     }
 
-    pub async fn fetch_following_messages(&self, chat_id: impl Into<&String>, turn_id: impl Into<&String>, pinned_only: bool) -> Result<Vec<Turn>, RequesterError> {
-        let chat_id = chat_id.into();
-        let target_turn_id = turn_id.into();
+    /// The turns after `turn_id`, newest first, fetching only as many pages as it takes to reach it.
+    pub async fn fetch_following_messages(&self, chat_id: impl AsRef<ChatIdRef>, turn_id: impl AsRef<TurnIdRef>, pinned_only: bool) -> Result<Vec<Turn>, RequesterError> {
+        let target_turn_id = turn_id.as_ref();
+        let mut turns = self.fetch_messages_stream(chat_id, pinned_only);
         let mut following_turns = Vec::new();
-        let mut next_token = None;
-    
-        loop {
-            let (turns, token) = self.fetch_messages(chat_id, pinned_only, next_token).await?;
-            if turns.is_empty() {
-                panic!("cannot fetch following messages");
-            }
-    
-            for turn in &turns {
-                if turn.id == *target_turn_id {
-                    return Ok(following_turns);
-                }
-                following_turns.push(turn.clone());
-            }
-    
-            if token.is_none() {
-                panic!("Cannot fetch following messages. May be turn_id is invalid?");
+
+        while let Some(turn) = turns.try_next().await? {
+            if turn.id == *target_turn_id {
+                return Ok(following_turns);
             }
-    
-            next_token = token;
+            following_turns.push(turn);
+        }
+
+        if following_turns.is_empty() {
+            Err(RequesterError::ApiError("cannot fetch following messages".to_string()))
+        } else {
+            Err(RequesterError::ValidationError("cannot fetch following messages, turn_id may be invalid".to_string()))
         }
     }
 
-    pub async fn update_chat_name(&self, chat_id: impl Into<&String>, name: impl Into<&String>) -> bool {
+    pub async fn update_chat_name(&self, chat_id: impl AsRef<ChatIdRef>, name: impl Into<&String>) -> bool {
+        let Ok(headers) = self.client.get_headers(None).await else { return false };
         let resp = self.requester.request_resp_async(
-            format!("https://neo.character.ai/chat/{}/update_name", chat_id.into()),
-            RequestOptions::new("PATCH", self.client.get_headers(None).await, Some(json!({ "name": name.into() }).to_string().into()))
+            self.requester.url(Host::Neo, format!("/chat/{}/update_name", chat_id.as_ref())),
+            RequestOptions::new("PATCH", headers, Some(json!({ "name": name.into() }).to_string().into()))
         ).await;
         
-        resp.map_or(false, |_| true)
+        resp.is_ok()
     }
 
-    pub async fn archive_chat(&self, chat_id: impl Into<&String>) -> bool {
+    pub async fn archive_chat(&self, chat_id: impl AsRef<ChatIdRef>) -> bool {
+        let Ok(headers) = self.client.get_headers(None).await else { return false };
         let resp = self.requester.request_async(
-            format!("https://neo.character.ai/chat/{}/archive", chat_id.into()),
-            RequestOptions::new("PATCH", self.client.get_headers(None).await, Some("{}".to_string().into()))
+            self.requester.url(Host::Neo, format!("/chat/{}/archive", chat_id.as_ref())),
+            RequestOptions::new("PATCH", headers, Some("{}".to_string().into()))
         ).await;
         
-        resp.map_or(false, |_| true)
+        resp.is_ok()
     }
     
-    pub async fn unarchive_chat(&self, chat_id: impl Into<&String>) -> bool {
+    pub async fn unarchive_chat(&self, chat_id: impl AsRef<ChatIdRef>) -> bool {
+        let Ok(headers) = self.client.get_headers(None).await else { return false };
         let resp = self.requester.request_async(
-            format!("https://neo.character.ai/chat/{}/unarchive", chat_id.into()),
-            RequestOptions::new("PATCH", self.client.get_headers(None).await, Some("{}".to_string().into()))
+            self.requester.url(Host::Neo, format!("/chat/{}/unarchive", chat_id.as_ref())),
+            RequestOptions::new("PATCH", headers, Some("{}".to_string().into()))
         ).await;
         
-        resp.map_or(false, |_| true)
+        resp.is_ok()
     }
     
-    pub async fn copy_chat(&self, chat_id: impl Into<&String>, end_turn_id: impl Into<&String>) -> Result<Option<String>, RequesterError> {
+    pub async fn copy_chat(&self, chat_id: impl AsRef<ChatIdRef>, end_turn_id: impl AsRef<TurnIdRef>) -> Result<Option<ChatId>, RequesterError> {
         let json: Value = self.requester.request_async(
-            format!("https://neo.character.ai/chat/{}/copy", chat_id.into()),
-            RequestOptions::new("POST", self.client.get_headers(None).await, Some(json!({ "end_turn_id": end_turn_id.into() }).to_string().into()))
+            self.requester.url(Host::Neo, format!("/chat/{}/copy", chat_id.as_ref())),
+            RequestOptions::new("POST", self.client.get_headers(None).await?, Some(json!({ "end_turn_id": end_turn_id.as_ref().as_str() }).to_string().into()))
         ).await?;
     
-        Ok(json.get("new_chat_id").and_then(|v| v.as_str()).map(|s| s.to_string()))
+        Ok(json.get("new_chat_id").and_then(|v| v.as_str()).map(ChatId::from))
     }
     
-    pub async fn create_chat(&self, character_id: impl Into<&String>, greeting: bool, model_type: Option<String>) -> Result<(Chat, Option<Turn>), RequesterError> {
-        let request_id = Uuid::new_v4().to_string();
-        let chat_id = Uuid::new_v4().to_string();
-        let character_id = character_id.into();
-    
-        let mut payload = json!({
-            "chat": {
-                "chat_id": chat_id,
-                "creator_id": self.client.data().await.id,
-                "visibility": "VISIBILITY_PRIVATE",
-                "character_id": character_id,
-                "type": "TYPE_ONE_ON_ONE",
+    /// Sends `command` over the neo websocket and streams the events answering it.
+    pub async fn send_command(&self, command: &WsCommand) -> Result<impl Stream<Item = Result<WsEvent, RequesterError>> + use<>, RequesterError> {
+        let message = command.to_message(Uuid::new_v4().to_string());
+        let stream = self.requester.ws_send_and_receive(&message, self.client.token().await?).await?;
+
+        Ok(stream.map(|raw| raw.and_then(|raw| parse(&raw))))
+    }
+
+    pub async fn create_chat(&self, character_id: impl AsRef<CharacterIdRef>, greeting: bool, model_type: Option<String>) -> Result<(Chat, Option<Turn>), RequesterError> {
+        let command = WsCommand::CreateChat(CreateChatPayload {
+            chat: NewChat {
+                chat_id: ChatId::new(Uuid::new_v4().to_string()),
+                creator_id: self.client.data().await.id,
+                visibility: "VISIBILITY_PRIVATE".to_string(),
+                character_id: character_id.as_ref().to_owned(),
+                chat_type: "TYPE_ONE_ON_ONE".to_string(),
+                preferred_model_type: model_type,
             },
-            "with_greeting": greeting,
+            with_greeting: greeting,
         });
-    
-        if let Some(model) = model_type {
-            payload["chat"]["preferred_model_type"] = json!(model);
-        }
         
         let mut new_chat: Option<Chat> = None;
         let mut greeting_turn: Option<Turn> = None;
         
-        let json = json!({
-            "command": "create_chat",
-            "request_id": request_id,
-            "payload": payload,
-        });
-        let stream = self.requester.ws_send_and_receive(&json, self.client.token().await).await?;
+        let stream = self.send_command(&command).await?;
         pin!(stream);
     
-        while let Some(raw) = stream.next().await {
-            let raw = raw.expect("WebSocket connection error");
-    
-            match raw.get("command").and_then(|v| v.as_str()) {
-                Some("create_chat_response") => {
-                    new_chat = Some(Chat::from_json(&raw["chat"]));
+        while let Some(event) = stream.next().await {
+            match event? {
+                WsEvent::CreateChatResponse { chat } => {
+                    new_chat = Some(chat);
                     if !greeting {
                         break;
                     }
                 },
-                Some("add_turn") => {
-                    greeting_turn = Some(Turn::from_json(&raw["turn"]));
+                WsEvent::AddTurn { turn } => {
+                    greeting_turn = Some(turn);
                     break;
                 },
-                Some("neo_error") => {
-                    let comment = raw.get("comment").and_then(|v| v.as_str()).unwrap_or("");
-                    return Err(RequesterError::WsError(format!("cannot create a new chat: {}", comment)));
+                WsEvent::NeoError { comment } => {
+                    return Err(RequesterError::ApiError(format!("cannot create a new chat: {}", comment)));
                 },
                 _ => {}
             }
         }
     
-        if new_chat.is_none() || (greeting && greeting_turn.is_none()) {
-            panic!("Cannot create a new chat.");
+        match new_chat {
+            Some(chat) if !greeting || greeting_turn.is_some() => Ok((chat, greeting_turn)),
+            _ => Err(RequesterError::WsError("cannot create a new chat: connection closed before the chat was created".to_string()))
         }
-    
-        Ok((new_chat.unwrap(), greeting_turn))
     }
     
-    pub async fn update_primary_candidate(&self, chat_id: impl Into<&String>, turn_id: impl Into<&String>, candidate_id: impl Into<&String>) -> bool {
-        let json = json!({
-            "command": "update_primary_candidate",
-            "origin_id": "web-next",
-            "payload": {
-                "candidate_id": candidate_id.into(),
-                "turn_key": { "chat_id": chat_id.into(), "turn_id": turn_id.into() },
-            }
+    pub async fn update_primary_candidate(&self, chat_id: impl AsRef<ChatIdRef>, turn_id: impl AsRef<TurnIdRef>, candidate_id: impl AsRef<CandidateIdRef>) -> bool {
+        let command = WsCommand::UpdatePrimaryCandidate(UpdatePrimaryCandidatePayload {
+            candidate_id: candidate_id.as_ref().to_owned(),
+            turn_key: TurnKey::new(chat_id.as_ref(), turn_id.as_ref()),
         });
-        let resp = self.requester.ws_send_and_receive(&json, self.client.token().await).await;
 
-        if let Ok(stream) = resp {
+        if let Ok(stream) = self.send_command(&command).await {
             pin!(stream);
-            while let Some(raw) = stream.next().await {
-                let raw = raw.expect("WebSocket connection error");
-        
-                match raw.get("command").and_then(|v| v.as_str()) {
-                    Some("ok") => {
+            while let Some(Ok(event)) = stream.next().await {
+                match event {
+                    WsEvent::Ok => {
                         return true;
                     },
-                    Some("neo_error") => {
+                    WsEvent::NeoError { .. } => {
                         return false;
                     },
                     _ => {}
@@ -639,155 +673,117 @@ impl ChatMethods {
         })
     }
 
-    async fn send_ws_internal(&self, json: Value, allow_add_turn: bool, return_immediately: bool) -> Result<impl Stream<Item = Turn>, RequesterError> {
+    async fn send_ws_internal(&self, command: WsCommand, allow_add_turn: bool, return_immediately: bool) -> Result<impl Stream<Item = Result<Turn, RequesterError>>, RequesterError> {
+        let stream = self.send_command(&command).await?;
+
         let ret_stream = stream! {
-            let resp = self.requester.ws_send_and_receive(&json, self.client.token().await).await;
-
-            if let Ok(stream) = resp {
-                pin!(stream);
-
-                while let Some(raw) = stream.next().await {
-                    let raw = raw.expect("WebSocket connection error");
-                    
-                    match raw.get("command").and_then(|v| v.as_str()) {
-                        val if val == Some("add_turn") && allow_add_turn || val == Some("update_turn") => {
-                            if raw["turn"]["author"]["is_human"].as_bool().unwrap_or(false) {
-                                continue;
-                            }
-            
-                            let turn = Turn::from_json(&raw["turn"]);
-                            yield turn.clone();
-            
-                            if turn.get_primary_candidate().map_or(false, |c| c.is_final) || return_immediately {
-                                break;
-                            }
-                        },
-                        Some("neo_error") => {
-                            let comment = raw["comment"].as_str().unwrap_or("");
-                            panic!("cannot send message: {}", comment);
-                        },
-                        _ => {}
+            pin!(stream);
+
+            while let Some(event) = stream.next().await {
+                let turn = match event {
+                    Ok(WsEvent::AddTurn { turn }) if allow_add_turn => turn,
+                    Ok(WsEvent::UpdateTurn { turn }) => turn,
+                    Ok(WsEvent::NeoError { comment }) => {
+                        yield Err(RequesterError::ApiError(format!("cannot send message: {}", comment)));
+                        break;
+                    },
+                    Ok(_) => continue,
+                    Err(err) => {
+                        yield Err(err);
+                        break;
                     }
+                };
+
+                if turn.author_is_human {
+                    continue;
+                }
+
+                let done = turn.get_primary_candidate().is_some_and(|c| c.is_final) || return_immediately;
+                yield Ok(turn);
+
+                if done {
+                    break;
                 }
             }
         };
-    
+
         Ok(ret_stream)
     }
 
-    async fn flatten_stream_internal<T>(&self, stream: impl Stream<Item = T>) -> Result<T, RequesterError> {
+    async fn flatten_stream_internal<T>(&self, stream: impl Stream<Item = Result<T, RequesterError>>) -> Result<T, RequesterError> {
         pin!(stream);
 
-        let mut last_t = Err(RequesterError::RequestFailed("stream is empty".to_string()));
+        let mut last_t = Err(RequesterError::WsError("stream ended without a response".to_string()));
 
         while let Some(t) = stream.next().await {
-            last_t = Ok(t);
+            last_t = Ok(t?);
         }
         
         last_t
     }
     
-    pub async fn send_message_stream(&self, character_id: impl Into<&String>, chat_id: impl Into<&String>, text: impl Into<&String>) -> Result<impl Stream<Item = Turn>, RequesterError> {
-        let candidate_id = Uuid::new_v4().to_string();
-        let turn_id = Uuid::new_v4().to_string();
-        let request_id = Uuid::new_v4().to_string();
+    pub async fn send_message_stream(&self, character_id: impl AsRef<CharacterIdRef>, chat_id: impl AsRef<ChatIdRef>, text: impl Into<&String>) -> Result<impl Stream<Item = Result<Turn, RequesterError>>, RequesterError> {
+        let candidate_id = CandidateId::new(Uuid::new_v4().to_string());
         
-        Ok(self.send_ws_internal(json!({
-            "command": "create_and_generate_turn",
-            "origin_id": "web-next",
-            "payload": {
-                "character_id": character_id.into(),
-                "num_candidates": 1,
-                "previous_annotations": self.default_annotations(),
-                "selected_language": "",
-                "tts_enabled": false,
-                "turn": {
-                    "author": {
-                        "author_id": self.client.data().await.id,
-                        "is_human": true,
-                        "name": "",
-                    },
-                    "candidates": [{
-                        "candidate_id": candidate_id,
-                        "raw_content": text.into(),
-                    }],
-                    "primary_candidate_id": candidate_id,
-                    "turn_key": { "chat_id": chat_id.into(), "turn_id": turn_id },
-                },
-                "user_name": ""
+        self.send_ws_internal(WsCommand::CreateAndGenerateTurn(CreateAndGenerateTurnPayload {
+            character_id: character_id.as_ref().to_owned(),
+            num_candidates: 1,
+            previous_annotations: self.default_annotations(),
+            selected_language: "".to_string(),
+            tts_enabled: false,
+            turn: NewTurn {
+                author: NewAuthor { author_id: self.client.data().await.id, is_human: true, name: "".to_string() },
+                candidates: vec![NewCandidate { candidate_id: candidate_id.clone(), raw_content: text.into().clone() }],
+                primary_candidate_id: candidate_id,
+                turn_key: TurnKey::new(chat_id.as_ref(), Uuid::new_v4().to_string()),
             },
-            "request_id": request_id,
-        }), true, false).await?)
+            user_name: "".to_string(),
+        }), true, false).await
     }
     
-    pub async fn send_message(&self, character_id: impl Into<&String>, chat_id: impl Into<&String>, text: impl Into<&String>) -> Result<Turn, RequesterError> {
+    pub async fn send_message(&self, character_id: impl AsRef<CharacterIdRef>, chat_id: impl AsRef<ChatIdRef>, text: impl Into<&String>) -> Result<Turn, RequesterError> {
         self.flatten_stream_internal(self.send_message_stream(character_id, chat_id, text).await?).await
     }
 
-    pub async fn retry_response_stream(&self, character_id: impl Into<&String>, chat_id: impl Into<&String>, turn_id: impl Into<&String>) -> Result<impl Stream<Item = Turn>, RequesterError> {
-        let request_id = Uuid::new_v4().to_string();
-        
-        Ok(self.send_ws_internal(json!({
-            "command": "generate_turn_candidate",
-            "origin_id": "web-next",
-            "payload": {
-                "character_id": character_id.into(),
-                "previous_annotations": self.default_annotations(),
-                "selected_language": "",
-                "tts_enabled": false,
-                "turn_key": { "chat_id": chat_id.into(), "turn_id": turn_id.into() },
-                "user_name": ""
-            },
-            "request_id": request_id
-        }), true, false).await?)
+    pub async fn retry_response_stream(&self, character_id: impl AsRef<CharacterIdRef>, chat_id: impl AsRef<ChatIdRef>, turn_id: impl AsRef<TurnIdRef>) -> Result<impl Stream<Item = Result<Turn, RequesterError>>, RequesterError> {
+        self.send_ws_internal(WsCommand::GenerateTurnCandidate(GenerateTurnCandidatePayload {
+            character_id: character_id.as_ref().to_owned(),
+            previous_annotations: self.default_annotations(),
+            selected_language: "".to_string(),
+            tts_enabled: false,
+            turn_key: TurnKey::new(chat_id.as_ref(), turn_id.as_ref()),
+            user_name: "".to_string(),
+        }), true, false).await
     }
 
-    pub async fn retry_response(&self, character_id: impl Into<&String>, chat_id: impl Into<&String>, turn_id: impl Into<&String>) -> Result<Turn, RequesterError> {
+    pub async fn retry_response(&self, character_id: impl AsRef<CharacterIdRef>, chat_id: impl AsRef<ChatIdRef>, turn_id: impl AsRef<TurnIdRef>) -> Result<Turn, RequesterError> {
         self.flatten_stream_internal(self.retry_response_stream(character_id, chat_id, turn_id).await?).await
     }
 
-    pub async fn edit_message(&self, chat_id: impl Into<&String>, turn_id: impl Into<&String>, candidate_id: impl Into<&String>, text: impl Into<&String>) -> Result<Turn, RequesterError> {
-        let request_id = Uuid::new_v4().to_string();
-
-        self.flatten_stream_internal(self.send_ws_internal(json!({
-            "command": "edit_turn_candidate",
-            "origin_id": "web-next",
-            "payload": {
-                "new_candidate_raw_content": text.into(),
-                "current_candidate_id": candidate_id.into(),
-                "turn_key": { "chat_id": chat_id.into(), "turn_id": turn_id.into() }
-            },
-            "request_id": request_id
+    pub async fn edit_message(&self, chat_id: impl AsRef<ChatIdRef>, turn_id: impl AsRef<TurnIdRef>, candidate_id: impl AsRef<CandidateIdRef>, text: impl Into<&String>) -> Result<Turn, RequesterError> {
+        self.flatten_stream_internal(self.send_ws_internal(WsCommand::EditTurnCandidate(EditTurnCandidatePayload {
+            new_candidate_raw_content: text.into().clone(),
+            current_candidate_id: candidate_id.as_ref().to_owned(),
+            turn_key: TurnKey::new(chat_id.as_ref(), turn_id.as_ref()),
         }), true, true).await?).await
     }
 
-    pub async fn delete_messages(&self, chat_id: impl Into<&String>, turn_ids: Vec<&String>) -> bool {
-        let request_id = Uuid::new_v4().to_string();
-    
-        let ws_message = json!({
-            "command": "remove_turns",
-            "origin_id": "web-next",
-            "request_id": request_id,
-            "payload": {
-                "chat_id": chat_id.into(),
-                "turn_ids": turn_ids
-            }
+    pub async fn delete_messages(&self, chat_id: impl AsRef<ChatIdRef>, turn_ids: impl IntoIterator<Item = impl AsRef<TurnIdRef>>) -> bool {
+        let command = WsCommand::RemoveTurns(RemoveTurnsPayload {
+            chat_id: chat_id.as_ref().to_owned(),
+            turn_ids: turn_ids.into_iter().map(|id| id.as_ref().to_owned()).collect(),
         });
     
-        let resp = self.requester.ws_send_and_receive(&ws_message, self.client.token().await).await;
-        if let Ok(stream) = resp {
+        if let Ok(stream) = self.send_command(&command).await {
             pin!(stream);
             
-            while let Some(raw) = stream.next().await {
-                let raw = raw.expect("WebSocket connection error");
-                
-                match raw.get("command").and_then(|v| v.as_str()) {
-                    Some("remove_turns_response") => {
+            while let Some(Ok(event)) = stream.next().await {
+                match event {
+                    WsEvent::RemoveTurnsResponse => {
                         return true;
                     },
-                    Some("neo_error") => {
-                        let comment = raw["comment"].as_str().unwrap_or("");
-                        panic!("cannot send message: {}", comment);
+                    WsEvent::NeoError { .. } => {
+                        return false;
                     },
                     _ => {}
                 }
@@ -797,33 +793,22 @@ impl ChatMethods {
         false
     }
     
-    pub async fn set_turn_pin(&self, chat_id: impl Into<&String>, turn_id: impl Into<&String>, is_pinned: bool) -> bool {
-        let request_id = Uuid::new_v4().to_string();
-    
-        let ws_message = json!({
-            "command": "set_turn_pin",
-            "origin_id": "web-next",
-            "request_id": request_id,
-            "payload": {
-                "is_pinned": is_pinned,
-                "turn_key": { "chat_id": chat_id.into(), "turn_id": turn_id.into() }
-            }
+    pub async fn set_turn_pin(&self, chat_id: impl AsRef<ChatIdRef>, turn_id: impl AsRef<TurnIdRef>, is_pinned: bool) -> bool {
+        let command = WsCommand::SetTurnPin(SetTurnPinPayload {
+            is_pinned,
+            turn_key: TurnKey::new(chat_id.as_ref(), turn_id.as_ref()),
         });
     
-        let resp = self.requester.ws_send_and_receive(&ws_message, self.client.token().await).await;
-        if let Ok(stream) = resp {
+        if let Ok(stream) = self.send_command(&command).await {
             pin!(stream);
             
-            while let Some(raw) = stream.next().await {
-                let raw = raw.expect("WebSocket connection error");
-                
-                match raw.get("command").and_then(|v| v.as_str()) {
-                    Some("update_turn") => {
-                        return raw["turn"]["is_pinned"].as_bool().unwrap_or(!is_pinned) == is_pinned;
+            while let Some(Ok(event)) = stream.next().await {
+                match event {
+                    WsEvent::UpdateTurn { turn } => {
+                        return turn.is_pinned == is_pinned;
                     },
-                    Some("neo_error") => {
-                        let comment = raw["comment"].as_str().unwrap_or("");
-                        panic!("cannot send message: {}", comment);
+                    WsEvent::NeoError { .. } => {
+                        return false;
                     },
                     _ => {}
                 }
@@ -837,24 +822,24 @@ impl ChatMethods {
 #[derive(Clone)]
 pub struct CharacterMethods {
     requester: Arc<Requester>,
-    client: Arc<AsyncClient>
+    client: Arc<ClientState>
 }
 
 impl CharacterMethods {
-    pub fn new(requester: Arc<Requester>, client: Arc<AsyncClient>) -> Self {
+    pub(crate) fn new(requester: Arc<Requester>, client: Arc<ClientState>) -> Self {
         Self { requester, client }
     }
 
     pub async fn fetch_characters_by_category(&self) -> Result<HashMap<String, Vec<PartialCharacter>>, RequesterError> {
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/curated_categories/characters/",
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
+            self.requester.url(Host::Plus, "/chat/curated_categories/characters/"),
+            RequestOptions::new("GET", self.client.get_headers(None).await?, None)
         ).await?;
     
         let mut result = HashMap::new();
         for (k, v) in json.get("characters_by_curated_category").unwrap_or(&json!({})).as_object().unwrap_or(&serde_json::Map::new()) {
             let characters = v.as_array().unwrap_or(&vec![])
-                .iter().map(|c| PartialCharacter::from_json(c)).collect();
+                .iter().map(parse).collect::<Result<Vec<PartialCharacter>, RequesterError>>()?;
             result.insert(k.to_string(), characters);
         }
         Ok(result)
@@ -862,113 +847,119 @@ impl CharacterMethods {
     
     pub async fn fetch_recommended_characters(&self) -> Result<Vec<PartialCharacter>, RequesterError> {
         let json: Value = self.requester.request_async(
-            "https://neo.character.ai/recommendation/v1/user",
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
+            self.requester.url(Host::Neo, "/recommendation/v1/user"),
+            RequestOptions::new("GET", self.client.get_headers(None).await?, None)
         ).await?;
     
-        Ok(json.get("characters").unwrap_or(&json!([])).as_array().unwrap_or(&vec![]).iter().map(|c| PartialCharacter::from_json(c)).collect())
+        parse_list(&json, "characters")
     }
     
     pub async fn fetch_featured_characters(&self) -> Result<Vec<PartialCharacter>, RequesterError> {
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/characters/featured_v2/",
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
+            self.requester.url(Host::Plus, "/chat/characters/featured_v2/"),
+            RequestOptions::new("GET", self.client.get_headers(None).await?, None)
         ).await?;
     
-        Ok(json.get("characters").unwrap_or(&json!([])).as_array().unwrap_or(&vec![]).iter().map(|c| PartialCharacter::from_json(c)).collect())
+        parse_list(&json, "characters")
     }
 
-    pub async fn fetch_similar_characters(&self, character_id: impl Into<&String>) -> Result<Vec<PartialCharacter>, RequesterError> {
+    pub async fn fetch_similar_characters(&self, character_id: impl AsRef<CharacterIdRef>) -> Result<Vec<PartialCharacter>, RequesterError> {
         let json: Value = self.requester.request_async(
-            format!("https://neo.character.ai/recommendation/v1/character/{}", character_id.into()),
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
+            self.requester.url(Host::Neo, format!("/recommendation/v1/character/{}", character_id.as_ref())),
+            RequestOptions::new("GET", self.client.get_headers(None).await?, None)
         ).await?;
     
-        Ok(json.get("characters").unwrap_or(&json!([])).as_array().unwrap_or(&vec![]).iter().map(|c| PartialCharacter::from_json(c)).collect())
+        parse_list(&json, "characters")
     }
     
-    pub async fn fetch_character_info(&self, character_id: impl Into<&String>) -> Result<Character, RequesterError> {
+    pub async fn fetch_character_info(&self, character_id: impl AsRef<CharacterIdRef>) -> Result<Character, RequesterError> {
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/character/info/",
-            RequestOptions::new("POST", self.client.get_headers(None).await, Some(json!({ "external_id": character_id.into() }).to_string().into()))
+            self.requester.url(Host::Plus, "/chat/character/info/"),
+            RequestOptions::new("POST", self.client.get_headers(None).await?, Some(json!({ "external_id": character_id.as_ref().as_str() }).to_string().into()))
         ).await?;
     
         if json.get("status").unwrap_or(&json!("")).as_str().unwrap_or("") != "OK" {
-            panic!("{}", json.get("error").unwrap_or(&json!("")));
+            return Err(RequesterError::ApiError(format!("cannot fetch character info: {}", json.get("error").and_then(|v| v.as_str()).unwrap_or(""))));
         }
-        Ok(Character::from_json(json.get("character").unwrap_or(&json!({}))))
+        parse(json.get("character").unwrap_or(&json!({})))
     }
 
     pub async fn search_characters(&self, character_name: impl Into<&String>) -> Result<Vec<PartialCharacter>, RequesterError> {
-        let character_name = character_name.into();
-        let payload = json!({
-            "0": {
-                "json": {
-                    "searchQuery": urlencoding::encode(&character_name)
+        self.search_characters_stream(character_name).try_collect().await
+    }
+
+    pub fn search_characters_stream<'s>(&self, character_name: impl Into<&'s String>) -> Paginator<PartialCharacter> {
+        let search_query = urlencoding::encode(character_name.into()).into_owned();
+        paginate(self, move |methods, cursor| {
+            let mut payload = json!({
+                "0": {
+                    "json": {
+                        "searchQuery": search_query
+                    }
                 }
+            });
+            if let Some(cursor) = cursor {
+                payload["0"]["json"]["cursor"] = json!(cursor);
             }
-        });
-        
-        let json: Value = self.requester.request_async(
-            format!("https://character.ai/api/trpc/search.search?batch=1&input={}", payload.to_string()),
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
-        ).await?;
-    
-        Ok(json[0]["result"]["data"]["json"]["characters"].as_array().unwrap_or(&vec![]).iter().map(|c| PartialCharacter::from_json(c)).collect())
+
+            async move {
+                let json: Value = methods.requester.request_async(
+                    methods.requester.url(Host::Trpc, format!("/search.search?batch=1&input={}", payload)),
+                    RequestOptions::new("GET", methods.client.get_headers(None).await?, None)
+                ).await?;
+
+                let data = &json[0]["result"]["data"]["json"];
+                Ok(Page::new(parse_list(data, "characters")?, next_cursor(data, "/nextCursor")))
+            }
+        })
     }
     
     pub async fn search_creators(&self, creator_name: impl Into<&String>) -> Result<Vec<String>, RequesterError> {
         let json: Value = self.requester.request_async(
-            format!("https://plus.character.ai/chat/creators/search/?query={}", urlencoding::encode(&creator_name.into())),
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
+            self.requester.url(Host::Plus, format!("/chat/creators/search/?query={}", urlencoding::encode(creator_name.into()))),
+            RequestOptions::new("GET", self.client.get_headers(None).await?, None)
         ).await?;
     
         Ok(json.get("creators").unwrap_or(&json!([])).as_array().unwrap_or(&vec![]).iter().map(|c| c["name"].as_str().unwrap_or("").to_string()).collect())
     }
 
-    pub async fn add_like_to_character(&self, character_id: impl Into<&String>, like: Option<bool>) -> bool {
+    pub async fn add_like_to_character(&self, character_id: impl AsRef<CharacterIdRef>, like: Option<bool>) -> bool {
+        let Ok(headers) = self.client.get_headers(None).await else { return false };
         let resp = self.requester.request_async(
-            "https://plus.character.ai/chat/character/vote/",
-            RequestOptions::new("POST", self.client.get_headers(None).await, Some(json!({
-                "external_id": character_id.into(),
+            self.requester.url(Host::Plus, "/chat/character/vote/"),
+            RequestOptions::new("POST", headers, Some(json!({
+                "external_id": character_id.as_ref().as_str(),
                 "vote": like
             }).to_string().into()))
         ).await;
     
-        resp.map_or(false, |v| v.get("status").unwrap_or(&json!("")).as_str().unwrap_or("") == "OK")
+        resp.is_ok_and(|v| v.get("status").unwrap_or(&json!("")).as_str().unwrap_or("") == "OK")
     }
 
-    pub async fn create_character(&self, name: impl Into<&String>, greeting: impl Into<&String>, title: impl Into<&String>, description: impl Into<&String>, definition: impl Into<&String>, copyable: bool, visibility: Visibility, avatar_rel_path: impl Into<&String>, default_voice_id: impl Into<&String>) -> Result<Character, RequesterError> {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_character(&self, name: impl Into<&String>, greeting: impl Into<&String>, title: impl Into<&String>, description: impl Into<&String>, definition: impl Into<&String>, copyable: bool, visibility: Visibility, avatar_rel_path: impl Into<&String>, default_voice_id: impl AsRef<VoiceIdRef>) -> Result<Character, RequesterError> {
         let name = name.into();
         let greeting = greeting.into();
         let title = title.into();
         let description = description.into();
         let definition = definition.into();
     
-        if name.len() < 3 || name.len() > 20 {
-            panic!("name cannot be less than 3 characters or more than 20 (is {} characters)", name.len());
-        }
-        if greeting.len() < 3 || greeting.len() > 2048 {
-            panic!("greeting cannot be less than 3 characters or more than 2048 (is {} characters)", greeting.len());
-        }
-        if !title.is_empty() && (title.len() < 3 || title.len() > 50) {
-            panic!("title cannot be less than 3 characters or more than 50 (is {} characters)", title.len());
-        }
-        if description.len() > 500 {
-            panic!("description cannot be less than more than 500 (is {} characters)", description.len());
-        }
-        if definition.len() > 32000 {
-            panic!("definition cannot be more than 32000 (is {} characters)", definition.len());
+        validate_length("name", name, 3, 20)?;
+        validate_length("greeting", greeting, 3, 2048)?;
+        if !title.is_empty() {
+            validate_length("title", title, 3, 50)?;
         }
+        validate_length("description", description, 0, 500)?;
+        validate_length("definition", definition, 0, 32000)?;
         
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/character/create/",
-            RequestOptions::new("POST", self.client.get_headers(None).await, Some(json!({
+            self.requester.url(Host::Plus, "/chat/character/create/"),
+            RequestOptions::new("POST", self.client.get_headers(None).await?, Some(json!({
                 "avatar_rel_path": avatar_rel_path.into(),
                 "base_img_prompt": "",
                 "categories": [],
                 "copyable": copyable,
-                "default_voice_id": default_voice_id.into(),
+                "default_voice_id": default_voice_id.as_ref().as_str(),
                 "definition": definition,
                 "description": description,
                 "greeting": greeting,
@@ -983,47 +974,40 @@ impl CharacterMethods {
         ).await?;
     
         if json.get("status").unwrap_or(&json!("")).as_str().unwrap_or("") == "OK" {
-            Ok(Character::from_json(&json.get("character").unwrap_or(&json!({}))))
+            parse(json.get("character").unwrap_or(&json!({})))
         } else {
-            Err(RequesterError::RequestFailed("malformed JSON object received".to_string()))
+            Err(RequesterError::ApiError(format!("cannot create character: {}", json.get("error").and_then(|v| v.as_str()).unwrap_or(""))))
         }
     }
 
-    pub async fn edit_character(&self, character_id: impl Into<&String>, name: impl Into<&String>, greeting: impl Into<&String>, title: impl Into<&String>, description: impl Into<&String>, definition: impl Into<&String>, copyable: bool, visibility: Visibility, avatar_rel_path: impl Into<&String>, default_voice_id: impl Into<&String>) -> Result<Character, RequesterError> {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn edit_character(&self, character_id: impl AsRef<CharacterIdRef>, name: impl Into<&String>, greeting: impl Into<&String>, title: impl Into<&String>, description: impl Into<&String>, definition: impl Into<&String>, copyable: bool, visibility: Visibility, avatar_rel_path: impl Into<&String>, default_voice_id: impl AsRef<VoiceIdRef>) -> Result<Character, RequesterError> {
         let name = name.into();
         let greeting = greeting.into();
         let title = title.into();
         let description = description.into();
         let definition = definition.into();
     
-        if name.len() < 3 || name.len() > 20 {
-            panic!("name cannot be less than 3 characters or more than 20 (is {} characters)", name.len());
-        }
-        if greeting.len() < 3 || greeting.len() > 2048 {
-            panic!("greeting cannot be less than 3 characters or more than 2048 (is {} characters)", greeting.len());
-        }
-        if !title.is_empty() && (title.len() < 3 || title.len() > 50) {
-            panic!("title cannot be less than 3 characters or more than 50 (is {} characters)", title.len());
-        }
-        if description.len() > 500 {
-            panic!("description cannot be less than more than 500 (is {} characters)", description.len());
-        }
-        if definition.len() > 32000 {
-            panic!("definition cannot be more than 32000 (is {} characters)", definition.len());
+        validate_length("name", name, 3, 20)?;
+        validate_length("greeting", greeting, 3, 2048)?;
+        if !title.is_empty() {
+            validate_length("title", title, 3, 50)?;
         }
+        validate_length("description", description, 0, 500)?;
+        validate_length("definition", definition, 0, 32000)?;
         
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/character/update/",
-            RequestOptions::new("POST", self.client.get_headers(None).await, Some(json!({
+            self.requester.url(Host::Plus, "/chat/character/update/"),
+            RequestOptions::new("POST", self.client.get_headers(None).await?, Some(json!({
                 "archived": false,
                 "avatar_rel_path": avatar_rel_path.into(),
                 "base_img_prompt": "",
                 "categories": [],
                 "copyable": copyable,
-                "default_voice_id": default_voice_id.into(),
+                "default_voice_id": default_voice_id.as_ref().as_str(),
                 "definition": definition,
                 "description": description,
-                "external_id": character_id.into(),
+                "external_id": character_id.as_ref().as_str(),
                 "greeting": greeting,
                 "img_gen_enabled": false,
                 "name": name,
@@ -1035,9 +1019,9 @@ impl CharacterMethods {
         ).await?;
     
         if json.get("status").unwrap_or(&json!("")).as_str().unwrap_or("") == "OK" {
-            Ok(Character::from_json(&json.get("character").unwrap_or(&json!({}))))
+            parse(json.get("character").unwrap_or(&json!({})))
         } else {
-            Err(RequesterError::RequestFailed(format!("cannot edit character: {:?}", json.get("error").unwrap_or(&json!("")).to_string())))
+            Err(RequesterError::ApiError(format!("cannot edit character: {}", json.get("error").and_then(|v| v.as_str()).unwrap_or(""))))
         }
     }
 }
@@ -1045,36 +1029,42 @@ impl CharacterMethods {
 #[derive(Clone)]
 pub struct UtilsMethods {
     requester: Arc<Requester>,
-    client: Arc<AsyncClient>
+    client: Arc<ClientState>
 }
 
 impl UtilsMethods {
-    pub fn new(requester: Arc<Requester>, client: Arc<AsyncClient>) -> Self {
+    pub(crate) fn new(requester: Arc<Requester>, client: Arc<ClientState>) -> Self {
         Self { requester, client }
     }
 
-    pub async fn fetch_voice(&self, voice_id: impl Into<&String>) -> Result<Voice, RequesterError> {
+    pub async fn fetch_voice(&self, voice_id: impl AsRef<VoiceIdRef>) -> Result<Voice, RequesterError> {
         let json: Value = self.requester.request_async(
-            format!("https://neo.character.ai/multimodal/api/v1/voices/{}", voice_id.into()),
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
+            self.requester.url(Host::Neo, format!("/multimodal/api/v1/voices/{}", voice_id.as_ref())),
+            RequestOptions::new("GET", self.client.get_headers(None).await?, None)
         ).await?;
     
-        Ok(Voice::from_json(&json.get("voice").unwrap_or(&json!({}))))
+        parse(json.get("voice").unwrap_or(&json!({})))
     }
 
     pub async fn search_voices(&self, voice_name: impl Into<&String>) -> Result<Vec<Voice>, RequesterError> {
-        let json: Value = self.requester.request_async(
-            format!("https://neo.character.ai/multimodal/api/v1/voices/search?query={}", urlencoding::encode(voice_name.into())),
-            RequestOptions::new("GET", self.client.get_headers(None).await, None)
-        ).await?;
-    
-        Ok(json.get("voices").unwrap_or(&json!([])).as_array().unwrap_or(&vec![]).iter().map(|v| Voice::from_json(v)).collect())
+        self.search_voices_stream(voice_name).try_collect().await
+    }
+
+    pub fn search_voices_stream<'s>(&self, voice_name: impl Into<&'s String>) -> Paginator<Voice> {
+        let url = self.requester.url(Host::Neo, format!("/multimodal/api/v1/voices/search?query={}", urlencoding::encode(voice_name.into())));
+        paginate(self, move |methods, cursor| {
+            let url = with_cursor(url.clone(), "pageToken", cursor.as_deref());
+            async move {
+                let json: Value = methods.requester.request_async(url, RequestOptions::new("GET", methods.client.get_headers(None).await?, None)).await?;
+                Ok(Page::new(parse_list(&json, "voices")?, next_cursor(&json, "/nextPageToken")))
+            }
+        })
     }
     
     pub async fn generate_image(&self, prompt: &str, num_candidates: Option<u8>) -> Result<Vec<String>, RequesterError> {
         let json: Value = self.requester.request_async(
-            "https://plus.character.ai/chat/character/generate-avatar-options",
-            RequestOptions::new("POST", self.client.get_headers(None).await, Some(json!({
+            self.requester.url(Host::Plus, "/chat/character/generate-avatar-options"),
+            RequestOptions::new("POST", self.client.get_headers(None).await?, Some(json!({
                 "prompt": prompt,
                 "num_candidates": num_candidates.unwrap_or(4),
                 "model_version": "v1"
@@ -1086,8 +1076,8 @@ impl UtilsMethods {
 
     pub async fn upload_avatar(&self, data: Vec<u8>, mime_type: String, check_image: bool) -> Result<Avatar, RequesterError> {
         let json: Value = self.requester.request_async(
-            "https://character.ai/api/trpc/user.uploadAvatar?batch=1",
-            RequestOptions::new("POST", self.client.get_headers(None).await, Some(json!({
+            self.requester.url(Host::Trpc, "/user.uploadAvatar?batch=1"),
+            RequestOptions::new("POST", self.client.get_headers(None).await?, Some(json!({
                 "0": {
                     "json": {
                         "imageDataUrl": format!("data:{};base64,{}", mime_type, general_purpose::STANDARD.encode(&data))
@@ -1102,37 +1092,32 @@ impl UtilsMethods {
 
             if check_image {
                 let image_req = self.requester.request_async(
-                    avatar.get_default_url(),
-                    RequestOptions::new("GET", self.client.get_headers(None).await, None)
+                    avatar.get_url_on(self.requester.config().base_url(Host::Media), 400, false),
+                    RequestOptions::new("GET", self.client.get_headers(None).await?, None)
                 ).await;
 
                 match image_req {
                     Ok(_) => Ok(avatar),
-                    Err(err) => panic!("Cannot upload avatar. {}", err),
+                    Err(err) => Err(RequesterError::ApiError(format!("cannot upload avatar: {}", err))),
                 }
             } else {
                 Ok(avatar)
             }
         } else {
-            panic!("Cannot upload avatar. Invalid response.");
+            Err(RequesterError::decode("cannot upload avatar: response did not contain a file name", &json))
         }
     }
 
     pub async fn upload_voice(&self, data: Vec<u8>, mime_type: String, name: impl Into<&String>, description: Option<String>, visibility: Option<Visibility>) -> Result<Voice, RequesterError> {
         let name = name.into();
-        let description = description.unwrap_or_else(|| "".to_string());
-        let visibility = visibility.unwrap_or_else(|| Visibility::Hidden);
+        let description = description.unwrap_or_default();
+        let visibility = visibility.unwrap_or(Visibility::Hidden);
     
-        if name.len() < 3 || name.len() > 20 {
-            panic!("mame cannot be less than 3 characters or more than 20 characters (is {} characters)", name.len());
-        }
-    
-        if description.len() > 120 {
-            panic!("description cannot be more than 120 characters (is {} characters)", description.len());
-        }
+        validate_length("name", name, 3, 20)?;
+        validate_length("description", &description, 0, 120)?;
     
         if visibility == Visibility::Unlisted {
-            panic!("visibility cannot be Unlisted");
+            return Err(RequesterError::ValidationError("visibility cannot be Unlisted".to_string()));
         }
     
         let boundary = format!("---------------------------{}", rand::rng().random::<u128>());
@@ -1155,32 +1140,32 @@ impl UtilsMethods {
                     "previewText": "Good day! Here to make life a little less complicated.",
                     "audioSourceType": "file"
                 }
-            }).to_string(),
+            }),
             boundary
         ).as_bytes());
     
         let headers = {
-            let mut h = self.client.get_headers(None).await;
+            let mut h = self.client.get_headers(None).await?;
             h.insert("Content-Type".to_string(), format!("multipart/form-data; boundary={}", boundary));
             h
         };
     
         let json: Value = self.requester.request_async(
-            "https://neo.character.ai/multimodal/api/v1/voices/",
+            self.requester.url(Host::Neo, "/multimodal/api/v1/voices/"),
             RequestOptions::new("POST", headers, Some(body.into()))
         ).await?;
     
         if let Some(voice_data) = json.get("voice") {
-            let new_voice = Voice::from_json(voice_data);
+            let new_voice: Voice = parse(voice_data)?;
             Ok(self.edit_voice(new_voice.clone(), Some(name.clone()), Some(description), Some(visibility)).await?)
         } else {
-            panic!("Cannot upload voice. Invalid response.");
+            Err(RequesterError::decode("cannot upload voice: response did not contain a voice", &json))
         }
     }
 
     pub async fn edit_voice(&self, voice: impl Into<VoiceOrId>, name: Option<String>, description: Option<String>, visibility: Option<Visibility>) -> Result<Voice, RequesterError> {
         let voice = match voice.into() {
-            VoiceOrId::Id(id) => self.fetch_voice(&id).await?,
+            VoiceOrId::Id(id) => self.fetch_voice(id).await?,
             VoiceOrId::Voice(v) => v,
         };
     
@@ -1189,34 +1174,30 @@ impl UtilsMethods {
         let visibility = visibility.unwrap_or_else(|| voice.visibility.clone());
     
         if name.is_empty() || description.is_empty() {
-            panic!("mame and description must be specified.");
-        }
-        if name.len() < 3 || name.len() > 20 {
-            panic!("mame cannot be less than 3 characters or more than 20 characters (is {} characters)", name.len());
-        }
-        if description.len() > 120 {
-            panic!("Cannot edit voice. Description must be no more than 120 characters.");
+            return Err(RequesterError::ValidationError("name and description must be specified".to_string()));
         }
+        validate_length("name", &name, 3, 20)?;
+        validate_length("description", &description, 0, 120)?;
     
         if visibility == Visibility::Unlisted {
-            panic!("Cannot edit voice. Visibility must be 'public' or 'private'.");
+            return Err(RequesterError::ValidationError("visibility must be Public or Hidden".to_string()));
         }
     
         let json: Value = self.requester.request_async(
-            format!("https://neo.character.ai/multimodal/api/v1/voices/{}", voice.id),
-            RequestOptions::new("PUT", self.client.get_headers(None).await, Some(json!({
+            self.requester.url(Host::Neo, format!("/multimodal/api/v1/voices/{}", voice.id)),
+            RequestOptions::new("PUT", self.client.get_headers(None).await?, Some(json!({
                 "voice": {
                     "audioSourceType": "file",
-                    "backendId": voice.id,
+                    "backendId": voice.id.as_str(),
                     "backendProvider": "cai",
                     "creatorInfo": {
-                        "id": voice.creator_id,
+                        "id": voice.creator_id.as_ref().map(UserId::as_str),
                         "source": "user",
                         "username": "",
                     },
                     "description": description,
                     "gender": voice.gender.to_string(),
-                    "id": voice.id,
+                    "id": voice.id.as_str(),
                     "internalStatus": "draft",
                     "lastUpdateTime": "0001-01-01T00:00:00Z",
                     "name": name,
@@ -1227,26 +1208,27 @@ impl UtilsMethods {
             }).to_string().into()))
         ).await?;
     
-        Ok(Voice::from_json(&json.get("voice").unwrap_or(&json!({}))))
+        parse(json.get("voice").unwrap_or(&json!({})))
     }
 
-    pub async fn delete_voice(&self, voice_id: impl Into<&String>) -> bool {
+    pub async fn delete_voice(&self, voice_id: impl AsRef<VoiceIdRef>) -> bool {
+        let Ok(headers) = self.client.get_headers(None).await else { return false };
         let resp = self.requester.request_resp_async(
-            format!("https://neo.character.ai/multimodal/api/v1/voices/{}", voice_id.into()),
-            RequestOptions::new("DELETE", self.client.get_headers(None).await, None)
+            self.requester.url(Host::Neo, format!("/multimodal/api/v1/voices/{}", voice_id.as_ref())),
+            RequestOptions::new("DELETE", headers, None)
         ).await;
         
-        resp.map_or(false, |_| true)
+        resp.is_ok()
     }
 
-    pub async fn generate_speech(&self, chat_id: impl Into<&String>, turn_id: impl Into<&String>, candidate_id: impl Into<&String>, voice_id: impl Into<&String>, return_url: bool) -> Result<Result<Vec<u8>, String>, RequesterError> {
+    pub async fn generate_speech(&self, chat_id: impl AsRef<ChatIdRef>, turn_id: impl AsRef<TurnIdRef>, candidate_id: impl AsRef<CandidateIdRef>, voice_id: impl AsRef<VoiceIdRef>, return_url: bool) -> Result<Result<Vec<u8>, String>, RequesterError> {
         let json: Value = self.requester.request_async(
-            "https://neo.character.ai/multimodal/api/v1/memo/replay",
-            RequestOptions::new("POST", self.client.get_headers(None).await, Some(json!({
-                "candidateId": candidate_id.into(),
-                "roomId": chat_id.into(),
-                "turnId": turn_id.into(),
-                "voiceId": voice_id.into(),
+            self.requester.url(Host::Neo, "/multimodal/api/v1/memo/replay"),
+            RequestOptions::new("POST", self.client.get_headers(None).await?, Some(json!({
+                "candidateId": candidate_id.as_ref().as_str(),
+                "roomId": chat_id.as_ref().as_str(),
+                "turnId": turn_id.as_ref().as_str(),
+                "voiceId": voice_id.as_ref().as_str(),
             }).to_string().into()))
         ).await?;
     
@@ -1259,24 +1241,132 @@ impl UtilsMethods {
 
             let audio_data = self.requester.request_resp_async(
                 audio_url.to_string(),
-                RequestOptions::new("GET", self.client.get_headers(None).await, None)
+                RequestOptions::new("GET", self.client.get_headers(None).await?, None)
             ).await?;
 
-            match audio_data.bytes().await {
-                Ok(bytes) => Ok(Ok(bytes.to_vec())),
-                Err(data) => Err(RequesterError::RequestFailed(format!("could not retrieve bytes from generated voice url: {:?}", data)))
-            }
+            Ok(Ok(audio_data.body))
         } else {
-            panic!("could not generate speech: {}", json.get("error").and_then(|e| e.get("message")).and_then(|m| m.as_str()).unwrap_or(""));
+            Err(RequesterError::ApiError(format!("could not generate speech: {}", json.get("error").and_then(|e| e.get("message")).and_then(|m| m.as_str()).unwrap_or(""))))
         }
     }
 
     pub async fn ping(&self) -> bool {
+        let Ok(headers) = self.client.get_headers(None).await else { return false };
         let resp = self.requester.request_resp_async(
-            "https://neo.character.ai/ping/",
-            RequestOptions::new("GET", self.client.get_headers(None).await, None),
+            self.requester.url(Host::Neo, "/ping/"),
+            RequestOptions::new("GET", headers, None),
         ).await;
 
-        resp.map_or(false, |v| v.status() == 200)
+        resp.is_ok_and(|v| v.status() == 200)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::AsyncClient;
+
+    use super::*;
+
+    fn profile() -> Value {
+        json!({ "user": { "user": { "id": 1, "username": "someone", "account": { "name": "Someone" } } } })
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn fetch_profile_offline() {
+        let transport = MemoryTransport::new();
+        transport.respond_json("GET", "/chat/user/", profile());
+        let client = AsyncClient::with_requester(Some("token".to_string()), Requester::with_transport(RequesterConfig::default(), transport.clone())).await;
+
+        let account = client.account().fetch_profile().await.unwrap();
+        assert_eq!(account.username, "someone");
+        assert_eq!(client.data().await, account);
+        assert_eq!(transport.requests()[0].headers["authorization"], "Token token");
+    }
+
+    #[tokio::test]
+    async fn send_message_offline() {
+        let transport = MemoryTransport::new();
+        transport.respond_json("GET", "/chat/user/", profile());
+        transport.on_ws_command(|command| {
+            let turn = |text: &str, is_final: bool| json!({
+                "command": "update_turn",
+                "request_id": command["request_id"],
+                "turn": {
+                    "turn_key": { "chat_id": command["payload"]["turn"]["turn_key"]["chat_id"], "turn_id": "reply" },
+                    "author": { "author_id": "2", "name": "Bot", "is_human": false },
+                    "primary_candidate_id": "c",
+                    "candidates": [{ "candidate_id": "c", "raw_content": text, "is_final": is_final }]
+                }
+            });
+            vec![turn("Hel", false), turn("Hello!", true)]
+        });
+        let client = AsyncClient::with_requester(Some("token".to_string()), Requester::with_transport(RequesterConfig::default(), transport.clone())).await;
+
+        let turn = client.chat().send_message(&"character".to_string(), &"chat".to_string(), &"Hi".to_string()).await.unwrap();
+        assert_eq!(turn.chat_id, "chat");
+        assert_eq!(turn.get_primary_candidate().unwrap().text, "Hello!");
+
+        let sent = transport.ws_commands();
+        assert_eq!(sent[0]["command"], "create_and_generate_turn");
+        assert_eq!(sent[0]["payload"]["turn"]["candidates"][0]["raw_content"], "Hi");
+    }
+
+    #[tokio::test]
+    async fn raw_calls_offline() {
+        let transport = MemoryTransport::new();
+        transport.respond_json("GET", "/chat/user/", profile());
+        transport.respond_json("POST", "/chat/something/new/", json!({ "status": "OK" }));
+        transport.on_ws_command(|command| vec![
+            json!({ "command": "something_new_response", "request_id": command["request_id"], "x": command["payload"]["x"] }),
+            json!({ "command": "neo_error", "request_id": command["request_id"], "comment": "done" }),
+        ]);
+        let client = AsyncClient::with_requester(Some("token".to_string()), Requester::with_transport(RequesterConfig::default(), transport.clone())).await;
+
+        let json = client.raw_request("POST", Host::Plus, "/chat/something/new/", Some(json!({ "a": 1 }))).await.unwrap();
+        assert_eq!(json["status"], "OK");
+        let request = transport.requests().pop().unwrap();
+        assert_eq!(request.headers["authorization"], "Token token");
+        assert_eq!(request.json().unwrap()["a"], 1);
+
+        let events = client.raw_ws_command("something_new", json!({ "x": 5 })).await.unwrap();
+        pin!(events);
+        match events.next().await.unwrap().unwrap() {
+            WsEvent::Unknown(json) => assert_eq!(json["x"], 5),
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert_eq!(events.next().await.unwrap().unwrap(), WsEvent::NeoError { comment: "done".to_string() });
+        assert_eq!(transport.ws_commands()[0]["command"], "something_new");
+    }
+
+    #[tokio::test]
+    async fn messages_are_paginated_offline() {
+        let turn = |id: &str, is_pinned: bool| json!({
+            "turn_key": { "chat_id": "chat", "turn_id": id },
+            "author": { "author_id": "1", "name": "Someone", "is_human": true },
+            "candidates": [],
+            "is_pinned": is_pinned
+        });
+        let transport = MemoryTransport::new();
+        transport.respond_json("GET", "/chat/user/", profile());
+        transport.respond_json("GET", "/turns/chat/", json!({ "turns": [turn("3", false), turn("2", false)], "meta": { "next_token": "b" } }));
+        transport.respond_json("GET", "/turns/chat/?next_token=b", json!({ "turns": [turn("1", true)], "meta": {} }));
+        transport.respond_json("GET", "/chat/user/followers/?page=1", json!({ "followers": ["a"], "has_next_page": true }));
+        transport.respond_json("GET", "/chat/user/followers/?page=2", json!({ "followers": ["b"], "has_next_page": false }));
+        let client = AsyncClient::with_requester(Some("token".to_string()), Requester::with_transport(RequesterConfig::default(), transport.clone())).await;
+        let chat = client.chat();
+        let ids = |turns: Vec<Turn>| turns.into_iter().map(|t| t.id.into_string()).collect::<Vec<_>>();
+
+        let mut turns = chat.fetch_messages_stream("chat", false);
+        assert_eq!(turns.try_next().await.unwrap().unwrap().id, "3");
+        assert_eq!(transport.requests().iter().filter(|r| r.url.contains("/turns/")).count(), 1);
+        assert_eq!(turns.next_page().await.unwrap().unwrap().len(), 1);
+        assert_eq!(turns.cursor(), Some("b"));
+        assert_eq!(ids(chat.fetch_messages_stream("chat", false).resume_from("b").try_collect().await.unwrap()), ["1"]);
+
+        assert_eq!(ids(chat.fetch_all_messages("chat", false).await.unwrap()), ["3", "2", "1"]);
+        assert_eq!(ids(chat.fetch_all_messages("chat", true).await.unwrap()), ["1"]);
+        assert_eq!(ids(chat.fetch_following_messages("chat", "1", false).await.unwrap()), ["3", "2"]);
+
+        assert_eq!(client.account().fetch_followers().await.unwrap(), ["a", "b"]);
+    }
+}
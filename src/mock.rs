@@ -0,0 +1,650 @@
+use std::{collections::{HashMap, VecDeque}, net::SocketAddr, sync::{Arc, Mutex, MutexGuard, PoisonError}};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream}, task::JoinHandle};
+use tokio_tungstenite::{tungstenite::{handshake::derive_accept_key, protocol::{Message, Role}}, WebSocketStream};
+use uuid::Uuid;
+
+use crate::{requester::RequesterConfig, types::timestamp::Timestamp};
+
+/// The current time in the RFC 3339 format the neo API uses.
+fn now() -> String {
+    Timestamp::from_unix(Timestamp::now().unix_seconds(), 0).to_string()
+}
+
+fn id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| urlencoding::decode(value).map_or_else(|_| value.to_string(), |v| v.into_owned()))
+}
+
+struct State {
+    account: Value,
+    settings: Value,
+    personas: Vec<Value>,
+    characters: Vec<Value>,
+    voices: Vec<Value>,
+    chats: Vec<Value>,
+    /// Turns per chat, newest first like the neo API returns them.
+    turns: HashMap<String, Vec<Value>>,
+    replies: VecDeque<String>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            account: json!({
+                "id": 1,
+                "username": "mock_user",
+                "bio": "",
+                "is_human": true,
+                "account": { "name": "Mock User", "avatar_type": "DEFAULT", "avatar_file_name": "" }
+            }),
+            settings: json!({
+                "default_persona_id": "",
+                "discordSettings": {},
+                "modelPreferenceSettings": {},
+                "outputStyleSettings": {},
+                "personaOverrides": {}
+            }),
+            personas: vec![],
+            characters: vec![json!({
+                "external_id": MockServer::CHARACTER_ID,
+                "participant__name": "Mock",
+                "title": "A character that says what it's told",
+                "description": "Answers with scripted replies, or repeats what you say.",
+                "definition": "",
+                "greeting": "Hello! I'm a mock character.",
+                "avatar_file_name": "mock/avatar.webp",
+                "visibility": "PUBLIC",
+                "upvotes": 0,
+                "user__username": "mock_creator",
+                "participant__num_interactions": 0,
+                "participant__user__username": "internal_id:mock",
+                "copyable": false
+            })],
+            voices: vec![json!({
+                "id": MockServer::VOICE_ID,
+                "name": "Mock Voice",
+                "description": "A voice that doesn't exist",
+                "gender": "neutral",
+                "visibility": "PUBLIC",
+                "preview_text": "Hello!",
+                "creatorInfo": { "id": "1", "username": "mock_creator" },
+                "internal_status": "active"
+            })],
+            chats: vec![],
+            turns: HashMap::new(),
+            replies: VecDeque::new(),
+        }
+    }
+}
+
+impl State {
+    fn character(&self, id: &str) -> Option<&Value> {
+        self.characters.iter().find(|c| c["external_id"] == id)
+    }
+
+    /// Characters speak with numeric author ids on neo, like users do.
+    fn author_id(&self, character_id: &str) -> String {
+        let index = self.characters.iter().position(|c| c["external_id"] == character_id).unwrap_or(self.characters.len());
+        (1000 + index).to_string()
+    }
+
+    fn turn_mut(&mut self, turn_key: &Value) -> Option<&mut Value> {
+        self.turns.get_mut(turn_key["chat_id"].as_str()?)?.iter_mut().find(|t| t["turn_key"]["turn_id"] == turn_key["turn_id"])
+    }
+
+    /// The next scripted reply, or `text` echoed back if there is none.
+    fn reply(&mut self, text: &str) -> String {
+        self.replies.pop_front().unwrap_or_else(|| text.to_string())
+    }
+
+    fn last_human_text(&self, chat_id: &str) -> String {
+        self.turns.get(chat_id)
+            .and_then(|turns| turns.iter().find(|t| t["author"]["is_human"] == true))
+            .and_then(|t| t["candidates"][0]["raw_content"].as_str())
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    fn bot_turn(&self, chat_id: &str, character_id: &str, text: &str) -> Value {
+        let candidate_id = id();
+        json!({
+            "turn_key": { "chat_id": chat_id, "turn_id": id() },
+            "create_time": now(),
+            "last_update_time": now(),
+            "state": "STATE_OK",
+            "author": { "author_id": self.author_id(character_id), "name": self.character(character_id).map_or(Value::Null, |c| c["participant__name"].clone()), "is_human": false },
+            "is_pinned": false,
+            "primary_candidate_id": candidate_id,
+            "candidates": [{ "candidate_id": candidate_id, "raw_content": text, "is_final": true, "create_time": now() }]
+        })
+    }
+
+    fn push_turn(&mut self, turn: Value) {
+        if let Some(chat_id) = turn["turn_key"]["chat_id"].as_str() {
+            self.turns.entry(chat_id.to_string()).or_default().insert(0, turn);
+        }
+    }
+}
+
+/// A local stand-in for the Character.AI REST APIs and the neo websocket, for testing without network access or a
+/// real account.
+///
+/// Point a client at it with [`MockServer::config`] and authenticate with [`MockServer::TOKEN`]. Characters reply
+/// with the texts queued by [`MockServer::script`], or echo the message back once the script runs out. The server
+/// stops when dropped.
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl MockServer {
+    pub const TOKEN: &str = "mock-token";
+    /// The id of the character every mock server starts with.
+    pub const CHARACTER_ID: &str = "mock-character";
+    /// The id of the voice every mock server starts with.
+    pub const VOICE_ID: &str = "mock-voice";
+
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let server_state = Arc::clone(&state);
+        let task = tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(serve(socket, Arc::clone(&server_state)));
+            }
+        });
+
+        Ok(Self { addr, state, task })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// A config sending every host to this server.
+    pub fn config(&self) -> RequesterConfig {
+        RequesterConfig::new()
+            .with_plus_url(format!("http://{}/plus", self.addr))
+            .with_neo_url(format!("http://{}/neo", self.addr))
+            .with_trpc_url(format!("http://{}/trpc", self.addr))
+            .with_media_url(format!("http://{}/media", self.addr))
+            .with_ws_url(format!("ws://{}/ws/", self.addr))
+    }
+
+    /// Queues replies for the next generated messages, in order.
+    pub fn script(&self, replies: impl IntoIterator<Item = impl Into<String>>) {
+        lock(&self.state).replies.extend(replies.into_iter().map(Into::into));
+    }
+
+    /// Adds a character in the shape `/chat/character/info/` returns it.
+    pub fn add_character(&self, character: Value) {
+        lock(&self.state).characters.push(character);
+    }
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+struct Request {
+    method: String,
+    path: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or(Value::Null)
+    }
+}
+
+async fn read_request(socket: &mut TcpStream) -> Option<Request> {
+    let mut buf = Vec::new();
+    let mut chunk = [0; 8192];
+
+    let (head_len, method, path, headers) = loop {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut req = httparse::Request::new(&mut headers);
+        if let httparse::Status::Complete(len) = req.parse(&buf).ok()? {
+            let headers = req.headers.iter()
+                .map(|h| (h.name.to_lowercase(), String::from_utf8_lossy(h.value).into_owned()))
+                .collect::<HashMap<_, _>>();
+            break (len, req.method?.to_string(), req.path?.to_string(), headers);
+        }
+    };
+
+    let content_length = headers.get("content-length").and_then(|v| v.parse::<usize>().ok()).unwrap_or(0);
+    let mut body = buf.split_off(head_len);
+    while body.len() < content_length {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+
+    Some(Request { method, path, headers, body })
+}
+
+async fn serve(mut socket: TcpStream, state: Arc<Mutex<State>>) {
+    let Some(req) = read_request(&mut socket).await else { return };
+
+    if req.headers.get("upgrade").is_some_and(|v| v.eq_ignore_ascii_case("websocket")) {
+        let authorized = req.headers.get("cookie").is_some_and(|c| c.contains(&format!("Token {}", MockServer::TOKEN)));
+        let Some(key) = req.headers.get("sec-websocket-key").filter(|_| authorized) else {
+            let _ = socket.write_all(b"HTTP/1.1 401 Unauthorized\r\ncontent-length: 0\r\nconnection: close\r\n\r\n").await;
+            return;
+        };
+        let accept = derive_accept_key(key.as_bytes());
+        let handshake = format!("HTTP/1.1 101 Switching Protocols\r\nupgrade: websocket\r\nconnection: Upgrade\r\nsec-websocket-accept: {}\r\n\r\n", accept);
+        if socket.write_all(handshake.as_bytes()).await.is_ok() {
+            serve_ws(WebSocketStream::from_raw_socket(socket, Role::Server, None).await, state).await;
+        }
+        return;
+    }
+
+    let (status, body) = if req.path.starts_with("/media/") {
+        (200, Value::Null)
+    } else if req.headers.get("authorization").map(String::as_str) != Some(&format!("Token {}", MockServer::TOKEN)) {
+        (401, json!({ "error": "invalid token" }))
+    } else {
+        handle_http(&mut lock(&state), &req)
+    };
+
+    let body = body.to_string();
+    let reason = match status {
+        200 => "OK",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        _ => "Bad Request",
+    };
+    let response = format!("HTTP/1.1 {} {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}", status, reason, body.len(), body);
+    let _ = socket.write_all(response.as_bytes()).await;
+}
+
+fn not_found() -> (u16, Value) {
+    (404, json!({ "error": "not found" }))
+}
+
+fn handle_http(state: &mut State, req: &Request) -> (u16, Value) {
+    let (path, query) = req.path.split_once('?').unwrap_or((&req.path, ""));
+    let body = req.json();
+    let username = state.account["username"].clone();
+
+    let ok = |value: Value| (200, value);
+    match (req.method.as_str(), path) {
+        // Account
+        ("GET", "/plus/chat/user/") => ok(json!({ "user": { "user": state.account } })),
+        ("GET", "/plus/chat/user/settings/") => ok(state.settings.clone()),
+        ("GET", "/plus/chat/user/followers/") => ok(json!({ "followers": [] })),
+        ("GET", "/plus/chat/user/following/") => ok(json!({ "following": [] })),
+        ("GET", "/plus/chat/persona/") => {
+            let id = query_param(query, "id").unwrap_or_default();
+            state.personas.iter().find(|p| p["external_id"] == id.as_str()).cloned().map_or_else(not_found, ok)
+        }
+        ("GET", "/plus/chat/personas/") => ok(json!({ "personas": state.personas.iter().filter(|p| p["archived"] != true).collect::<Vec<_>>() })),
+        ("GET", "/plus/chat/chracters/") => ok(json!({ "characters": state.characters.iter().filter(|c| c["user__username"] == username).collect::<Vec<_>>() })),
+        ("GET", "/plus/chat/user/characters/upvoted/") => ok(json!({ "characters": [] })),
+        ("GET", "/plus/multimodal/api/v1/voices/user") => ok(json!({ "voices": state.voices.iter().filter(|v| v["creatorInfo"]["username"] == username).collect::<Vec<_>>() })),
+        ("POST", "/plus/chat/user/update_settings/") => {
+            state.settings = body;
+            let mut response = state.settings.clone();
+            response["success"] = json!(true);
+            ok(response)
+        }
+        ("POST", "/plus/chat/user/update/") => {
+            state.account["username"] = body["username"].clone();
+            state.account["bio"] = body["bio"].clone();
+            state.account["account"]["name"] = body["name"].clone();
+            state.account["account"]["avatar_type"] = body["avatar_type"].clone();
+            state.account["account"]["avatar_file_name"] = body.get("avatar_rel_path").cloned().unwrap_or(json!(""));
+            ok(json!({ "status": "OK" }))
+        }
+        ("POST", "/plus/chat/persona/create/") => {
+            let persona = json!({
+                "external_id": id(),
+                "participant__name": body["name"],
+                "greeting": body["greeting"],
+                "description": body["description"],
+                "definition": body["definition"],
+                "avatar_file_name": body["avatar_rel_path"],
+                "archived": false,
+                "author_username": username
+            });
+            state.personas.push(persona.clone());
+            ok(json!({ "status": "OK", "persona": persona }))
+        }
+        ("POST", "/plus/chat/persona/update/") => {
+            let Some(persona) = state.personas.iter_mut().find(|p| p["external_id"] == body["external_id"]) else { return not_found() };
+            for (field, from) in [("participant__name", "name"), ("greeting", "greeting"), ("description", "description"), ("definition", "definition"), ("avatar_file_name", "avatar_rel_path"), ("archived", "archived")] {
+                if let Some(value) = body.get(from) {
+                    persona[field] = value.clone();
+                }
+            }
+            ok(json!({ "status": "OK", "persona": persona }))
+        }
+
+        // User
+        ("POST", "/plus/chat/user/public/") => {
+            if body["username"] != username {
+                return ok(json!({ "public_user": null }));
+            }
+            ok(json!({ "public_user": {
+                "username": username,
+                "name": state.account["account"]["name"],
+                "bio": state.account["bio"],
+                "avatar_file_name": state.account["account"]["avatar_file_name"],
+                "num_following": 0,
+                "num_followers": 0,
+                "characters": state.characters.iter().filter(|c| c["user__username"] == username).collect::<Vec<_>>(),
+                "subscription_type": "NONE"
+            } }))
+        }
+        ("POST", "/plus/chat/user/follow/") | ("POST", "/plus/chat/user/unfollow/") => ok(json!({ "status": "OK" })),
+
+        // Characters
+        ("GET", "/plus/chat/curated_categories/characters/") => ok(json!({ "characters_by_curated_category": { "Featured": state.characters } })),
+        ("GET", "/plus/chat/characters/featured_v2/") | ("GET", "/neo/recommendation/v1/user") => ok(json!({ "characters": state.characters })),
+        ("GET", p) if p.starts_with("/neo/recommendation/v1/character/") => {
+            let id = p.trim_start_matches("/neo/recommendation/v1/character/");
+            ok(json!({ "characters": state.characters.iter().filter(|c| c["external_id"] != id).collect::<Vec<_>>() }))
+        }
+        ("POST", "/plus/chat/character/info/") => match body["external_id"].as_str().and_then(|id| state.character(id)) {
+            Some(character) => ok(json!({ "status": "OK", "character": character })),
+            None => ok(json!({ "status": "NOT_OK", "error": "character not found" })),
+        },
+        ("GET", "/trpc/search.search") => {
+            let input = query_param(query, "input").and_then(|input| serde_json::from_str::<Value>(&input).ok()).unwrap_or_default();
+            let search = input["0"]["json"]["searchQuery"].as_str().map(|q| urlencoding::decode(q).map_or_else(|_| q.to_string(), |q| q.into_owned())).unwrap_or_default().to_lowercase();
+            let characters = state.characters.iter().filter(|c| c["participant__name"].as_str().unwrap_or_default().to_lowercase().contains(&search)).collect::<Vec<_>>();
+            ok(json!([{ "result": { "data": { "json": { "characters": characters } } } }]))
+        }
+        ("GET", "/plus/chat/creators/search/") => {
+            let search = query_param(query, "query").unwrap_or_default();
+            let creators = state.characters.iter().filter_map(|c| c["user__username"].as_str()).filter(|name| name.contains(&search)).collect::<std::collections::BTreeSet<_>>();
+            ok(json!({ "creators": creators.into_iter().map(|name| json!({ "name": name })).collect::<Vec<_>>() }))
+        }
+        ("POST", "/plus/chat/character/vote/") => ok(json!({ "status": "OK" })),
+        ("POST", "/plus/chat/character/create/") | ("POST", "/plus/chat/character/update/") => {
+            let creating = path.ends_with("/create/");
+            let external_id = if creating { json!(id()) } else { body["external_id"].clone() };
+            let character = json!({
+                "external_id": external_id,
+                "participant__name": body["name"],
+                "title": body["title"],
+                "description": body["description"],
+                "definition": body["definition"],
+                "greeting": body["greeting"],
+                "avatar_file_name": body["avatar_rel_path"],
+                "visibility": body["visibility"],
+                "copyable": body["copyable"],
+                "default_voice_id": body["default_voice_id"],
+                "identifier": body["identifier"],
+                "upvotes": 0,
+                "user__username": username,
+                "participant__num_interactions": 0
+            });
+            if creating {
+                state.characters.push(character.clone());
+            } else if let Some(existing) = state.characters.iter_mut().find(|c| c["external_id"] == external_id) {
+                *existing = character.clone();
+            } else {
+                return ok(json!({ "status": "NOT_OK", "error": "character not found" }));
+            }
+            ok(json!({ "status": "OK", "character": character }))
+        }
+        ("POST", "/plus/chat/character/histories/") => ok(json!({ "histories": [] })),
+
+        // Utils
+        ("GET", "/neo/multimodal/api/v1/voices/search") => {
+            let search = query_param(query, "query").unwrap_or_default().to_lowercase();
+            let creator = query_param(query, "creatorInfo.username");
+            let voices = state.voices.iter()
+                .filter(|v| v["name"].as_str().unwrap_or_default().to_lowercase().contains(&search))
+                .filter(|v| creator.as_ref().is_none_or(|c| v["creatorInfo"]["username"] == c.as_str()))
+                .collect::<Vec<_>>();
+            ok(json!({ "voices": voices }))
+        }
+        ("POST", "/neo/multimodal/api/v1/voices/") => {
+            let voice = json!({
+                "id": id(),
+                "name": "",
+                "description": "",
+                "gender": "neutral",
+                "visibility": "PRIVATE",
+                "preview_text": "",
+                "creatorInfo": { "id": state.account["id"].to_string(), "username": username },
+                "internal_status": "draft"
+            });
+            state.voices.push(voice.clone());
+            ok(json!({ "voice": voice }))
+        }
+        (method, p) if p.starts_with("/neo/multimodal/api/v1/voices/") => {
+            let id = p.trim_start_matches("/neo/multimodal/api/v1/voices/");
+            let Some(i) = state.voices.iter().position(|v| v["id"] == id) else { return not_found() };
+            match method {
+                "GET" => ok(json!({ "voice": state.voices[i] })),
+                "PUT" => {
+                    let voice = &mut state.voices[i];
+                    for field in ["name", "description", "visibility"] {
+                        voice[field] = body["voice"][field].clone();
+                    }
+                    ok(json!({ "voice": voice }))
+                }
+                "DELETE" => {
+                    state.voices.remove(i);
+                    ok(json!({}))
+                }
+                _ => not_found(),
+            }
+        }
+        ("POST", "/neo/multimodal/api/v1/memo/replay") => ok(json!({ "replayUrl": "/media/replay.mp3" })),
+        ("POST", "/plus/chat/character/generate-avatar-options") => {
+            let n = body["num_candidates"].as_u64().unwrap_or(4);
+            ok(json!({ "result": (0..n).map(|i| json!({ "url": format!("https://characterai.io/mock/{}.webp", i) })).collect::<Vec<_>>() }))
+        }
+        ("POST", "/trpc/user.uploadAvatar") => ok(json!([{ "result": { "data": { "json": format!("uploaded/{}.webp", id()) } } }])),
+        ("GET", "/neo/ping/") => ok(json!({ "status": "pong" })),
+
+        // Chats
+        ("GET", "/neo/chats/") => {
+            let character_id = query_param(query, "character_ids").unwrap_or_default();
+            ok(json!({ "chats": state.chats.iter().filter(|c| c["character_id"] == character_id.as_str()).collect::<Vec<_>>() }))
+        }
+        ("GET", "/neo/chats/recent/") => ok(json!({ "chats": state.chats })),
+        ("GET", p) if p.starts_with("/neo/turns/") => {
+            let chat_id = p.trim_start_matches("/neo/turns/").trim_end_matches('/');
+            ok(json!({ "turns": state.turns.get(chat_id).cloned().unwrap_or_default(), "meta": { "next_token": null } }))
+        }
+        (method, p) if p.starts_with("/neo/chat/") => {
+            let rest = p.trim_start_matches("/neo/chat/");
+            let (chat_id, action) = rest.split_once('/').unwrap_or((rest, ""));
+            let Some(i) = state.chats.iter().position(|c| c["chat_id"] == chat_id) else { return not_found() };
+            match (method, action) {
+                ("GET", "") => ok(json!({ "chat": state.chats[i] })),
+                ("PATCH", "update_name") => {
+                    state.chats[i]["name"] = body["name"].clone();
+                    ok(json!({}))
+                }
+                ("PATCH", "archive") | ("PATCH", "unarchive") => ok(json!({})),
+                ("POST", "copy") => {
+                    let new_id = id();
+                    let mut chat = state.chats[i].clone();
+                    chat["chat_id"] = json!(new_id);
+                    state.chats.push(chat);
+                    let turns = state.turns.get(chat_id).cloned().unwrap_or_default();
+                    state.turns.insert(new_id.clone(), turns);
+                    ok(json!({ "new_chat_id": new_id }))
+                }
+                _ => not_found(),
+            }
+        }
+
+        _ => not_found(),
+    }
+}
+
+async fn serve_ws(mut ws: WebSocketStream<TcpStream>, state: Arc<Mutex<State>>) {
+    while let Some(Ok(message)) = ws.next().await {
+        let frames = match message {
+            Message::Text(text) => match serde_json::from_str::<Value>(&text) {
+                Ok(command) => handle_ws(&mut lock(&state), &command),
+                Err(_) => continue,
+            },
+            Message::Ping(_) => {
+                // The pong is queued by the read; make sure it goes out even if nothing else is sent.
+                let _ = ws.flush().await;
+                continue;
+            }
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        for frame in frames {
+            if ws.send(Message::Text(frame.to_string().into())).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn handle_ws(state: &mut State, command: &Value) -> Vec<Value> {
+    let request_id = &command["request_id"];
+    let payload = &command["payload"];
+    let frame = |name: &str, mut body: Value| {
+        body["command"] = json!(name);
+        body["request_id"] = request_id.clone();
+        body
+    };
+    let error = |comment: &str| vec![frame("neo_error", json!({ "comment": comment }))];
+
+    match command["command"].as_str().unwrap_or_default() {
+        "create_chat" => {
+            let chat = &payload["chat"];
+            let Some(character) = chat["character_id"].as_str().and_then(|id| state.character(id)).cloned() else { return error("character not found") };
+            let chat_id = chat["chat_id"].as_str().map_or_else(id, String::from);
+            let new_chat = json!({
+                "chat_id": chat_id,
+                "character_id": character["external_id"],
+                "character_name": character["participant__name"],
+                "character_avatar_uri": character["avatar_file_name"],
+                "creator_id": state.account["id"].to_string(),
+                "visibility": chat["visibility"],
+                "type": chat["type"],
+                "create_time": now(),
+                "preferred_model_type": chat["preferred_model_type"],
+                "state": "STATE_ACTIVE"
+            });
+            state.chats.push(new_chat.clone());
+            state.turns.entry(chat_id.clone()).or_default();
+
+            let mut frames = vec![frame("create_chat_response", json!({ "chat": new_chat }))];
+            if payload["with_greeting"] == true {
+                let turn = state.bot_turn(&chat_id, character["external_id"].as_str().unwrap_or_default(), character["greeting"].as_str().unwrap_or_default());
+                state.push_turn(turn.clone());
+                frames.push(frame("add_turn", json!({ "turn": turn })));
+            }
+            frames
+        }
+        "create_and_generate_turn" => {
+            let mut human = payload["turn"].clone();
+            let Some(chat_id) = human["turn_key"]["chat_id"].as_str().map(String::from) else { return error("missing turn_key") };
+            let Some(character_id) = payload["character_id"].as_str() else { return error("missing character_id") };
+            human["create_time"] = json!(now());
+            human["author"]["name"] = state.account["account"]["name"].clone();
+            human["candidates"][0]["is_final"] = json!(true);
+            state.push_turn(human.clone());
+
+            let text = human["candidates"][0]["raw_content"].as_str().unwrap_or_default().to_string();
+            let reply = state.reply(&text);
+            let turn = state.bot_turn(&chat_id, character_id, &reply);
+            state.push_turn(turn.clone());
+
+            // Stream the reply in two halves, the way the real server sends growing candidates.
+            let mut partial = turn.clone();
+            let half = reply.char_indices().nth(reply.chars().count() / 2).map_or(0, |(i, _)| i);
+            partial["candidates"][0]["raw_content"] = json!(&reply[..half]);
+            partial["candidates"][0]["is_final"] = json!(false);
+
+            vec![
+                frame("add_turn", json!({ "turn": human })),
+                frame("update_turn", json!({ "turn": partial })),
+                frame("update_turn", json!({ "turn": turn })),
+            ]
+        }
+        "generate_turn_candidate" => {
+            let chat_id = payload["turn_key"]["chat_id"].as_str().unwrap_or_default().to_string();
+            let text = state.last_human_text(&chat_id);
+            let reply = state.reply(&text);
+            let Some(turn) = state.turn_mut(&payload["turn_key"]) else { return error("turn not found") };
+
+            let candidate_id = id();
+            if let Some(candidates) = turn["candidates"].as_array_mut() {
+                candidates.push(json!({ "candidate_id": candidate_id, "raw_content": reply, "is_final": true, "create_time": now() }));
+            }
+            turn["primary_candidate_id"] = json!(candidate_id);
+            vec![frame("update_turn", json!({ "turn": turn }))]
+        }
+        "edit_turn_candidate" => {
+            let editor = json!({ "author_id": state.account["id"].to_string(), "name": state.account["account"]["name"] });
+            let Some(turn) = state.turn_mut(&payload["turn_key"]) else { return error("turn not found") };
+            let Some(candidate) = turn["candidates"].as_array_mut().and_then(|c| c.iter_mut().find(|c| c["candidate_id"] == payload["current_candidate_id"])) else { return error("candidate not found") };
+            candidate["raw_content"] = payload["new_candidate_raw_content"].clone();
+            candidate["editor"] = editor;
+            vec![frame("update_turn", json!({ "turn": turn }))]
+        }
+        "remove_turns" => {
+            let Some(turns) = payload["chat_id"].as_str().and_then(|id| state.turns.get_mut(id)) else { return error("chat not found") };
+            let removed = payload["turn_ids"].as_array().cloned().unwrap_or_default();
+            turns.retain(|t| !removed.contains(&t["turn_key"]["turn_id"]));
+            vec![frame("remove_turns_response", json!({ "chat_id": payload["chat_id"], "turn_ids": removed }))]
+        }
+        "set_turn_pin" => {
+            let Some(turn) = state.turn_mut(&payload["turn_key"]) else { return error("turn not found") };
+            turn["is_pinned"] = payload["is_pinned"].clone();
+            vec![frame("update_turn", json!({ "turn": turn }))]
+        }
+        "update_primary_candidate" => {
+            let Some(turn) = state.turn_mut(&payload["turn_key"]) else { return error("turn not found") };
+            turn["primary_candidate_id"] = payload["candidate_id"].clone();
+            vec![frame("ok", json!({ "chat_id": payload["turn_key"]["chat_id"] }))]
+        }
+        other => error(&format!("unknown command {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_rfc_3339_dates() {
+        let date = now();
+        assert_eq!(date.len(), 20);
+        assert!(date.starts_with("20") && date.ends_with('Z'));
+    }
+}
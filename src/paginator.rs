@@ -0,0 +1,152 @@
+use std::{collections::VecDeque, future::Future, pin::Pin, task::{ready, Context, Poll}};
+use futures_util::{future::{self, BoxFuture}, FutureExt, Stream};
+
+use crate::requester::RequesterError;
+
+/// One page of a list endpoint, and the cursor of the page after it, if there is one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, next_cursor: Option<String>) -> Self {
+        Self { items, next_cursor }
+    }
+}
+
+type FetchPage<T> = Box<dyn Fn(Option<String>) -> BoxFuture<'static, Result<Page<T>, RequesterError>> + Send + Sync>;
+
+/// Streams the items of a list endpoint, fetching each page only once the items before it have been taken.
+///
+/// The stream ends after the last page, after an empty page, or after the first error. [`Paginator::cursor`] is
+/// the cursor of the next page to fetch: save it after a [`Paginator::next_page`] and hand it to
+/// [`Paginator::resume_from`] later to carry on from there.
+pub struct Paginator<T> {
+    fetch: FetchPage<T>,
+    cursor: Option<String>,
+    items: VecDeque<T>,
+    pending: Option<BoxFuture<'static, Result<Page<T>, RequesterError>>>,
+    done: bool,
+}
+
+// Items are only ever moved out of the buffer, never pinned.
+impl<T> Unpin for Paginator<T> {}
+
+impl<T> Paginator<T> {
+    /// A paginator calling `fetch` with the cursor of every page it needs, starting with `None` for the first one.
+    pub fn new<F, Fut>(fetch: F) -> Self
+    where
+        F: Fn(Option<String>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Page<T>, RequesterError>> + Send + 'static,
+    {
+        Self { fetch: Box::new(move |cursor| fetch(cursor).boxed()), cursor: None, items: VecDeque::new(), pending: None, done: false }
+    }
+
+    /// Starts from the page at `cursor` rather than the first page. Has no effect once a page has been fetched.
+    pub fn resume_from(mut self, cursor: impl Into<String>) -> Self {
+        if self.pending.is_none() && self.items.is_empty() && !self.done {
+            self.cursor = Some(cursor.into());
+        }
+        self
+    }
+
+    /// The cursor of the next page to fetch, or `None` before the first page and after the last one.
+    pub fn cursor(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+
+    /// The items left of the current page, or the next page if they've all been taken.
+    pub async fn next_page(&mut self) -> Option<Result<Vec<T>, RequesterError>> {
+        future::poll_fn(|cx| self.poll_page(cx)).await
+    }
+
+    fn poll_page(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Vec<T>, RequesterError>>> {
+        if !self.items.is_empty() {
+            return Poll::Ready(Some(Ok(self.items.drain(..).collect())));
+        }
+        if self.done {
+            return Poll::Ready(None);
+        }
+
+        let pending = match &mut self.pending {
+            Some(pending) => pending,
+            None => self.pending.insert((self.fetch)(self.cursor.clone())),
+        };
+        let page = ready!(pending.poll_unpin(cx));
+        self.pending = None;
+
+        match page {
+            Ok(page) if page.items.is_empty() => {
+                self.done = true;
+                self.cursor = None;
+                Poll::Ready(None)
+            }
+            Ok(page) => {
+                self.done = page.next_cursor.is_none();
+                self.cursor = page.next_cursor;
+                Poll::Ready(Some(Ok(page.items)))
+            }
+            Err(err) => {
+                // The cursor is kept, so the failed page can be fetched again with `resume_from`.
+                self.done = true;
+                Poll::Ready(Some(Err(err)))
+            }
+        }
+    }
+}
+
+impl<T> Stream for Paginator<T> {
+    type Item = Result<T, RequesterError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some(item) = this.items.pop_front() {
+            return Poll::Ready(Some(Ok(item)));
+        }
+
+        match ready!(this.poll_page(cx)) {
+            Some(Ok(items)) => {
+                this.items = items.into();
+                Poll::Ready(this.items.pop_front().map(Ok))
+            }
+            Some(Err(err)) => Poll::Ready(Some(Err(err))),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use futures_util::{StreamExt, TryStreamExt};
+
+    use super::*;
+
+    /// Pages of two numbers up to 5, with the cursor being the first number of the page.
+    fn numbers(fetched: Arc<Mutex<Vec<Option<String>>>>) -> Paginator<u32> {
+        Paginator::new(move |cursor: Option<String>| {
+            fetched.lock().unwrap().push(cursor.clone());
+            let start = cursor.map_or(0, |c| c.parse().unwrap());
+            async move { Ok(Page::new((start..(start + 2).min(5)).collect(), (start + 2 < 5).then(|| (start + 2).to_string()))) }
+        })
+    }
+
+    #[tokio::test]
+    async fn pages_are_fetched_lazily_and_can_be_resumed() {
+        let fetched = Arc::new(Mutex::new(vec![]));
+        let mut paginator = numbers(fetched.clone());
+        assert_eq!(paginator.next().await.unwrap().unwrap(), 0);
+        assert_eq!(fetched.lock().unwrap().len(), 1);
+
+        assert_eq!(paginator.next_page().await.unwrap().unwrap(), [1]);
+        assert_eq!(paginator.next_page().await.unwrap().unwrap(), [2, 3]);
+        let cursor = paginator.cursor().unwrap().to_string();
+        assert_eq!(cursor, "4");
+
+        let rest: Vec<u32> = numbers(fetched.clone()).resume_from(cursor).try_collect().await.unwrap();
+        assert_eq!(rest, [4]);
+        assert_eq!(*fetched.lock().unwrap(), [None, Some("2".to_string()), Some("4".to_string())]);
+    }
+}
@@ -0,0 +1,118 @@
+use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+use futures_util::future;
+
+use crate::{client::AsyncClient, requester::*, types::{character::{Character, PartialCharacter}, ids::CharacterIdRef}};
+
+/// Clients for several accounts sharing one HTTP connection pool.
+///
+/// Every client keeps its own token, [`crate::types::user::Account`] and websocket session. Calls that don't
+/// depend on the account, such as looking up characters, can be spread over the accounts with
+/// [`ClientPool::next`].
+pub struct ClientPool {
+    clients: Vec<Arc<AsyncClient>>,
+    next: AtomicUsize,
+}
+
+impl ClientPool {
+    pub async fn new(tokens: impl IntoIterator<Item = impl Into<SecretToken>>) -> Result<Self, RequesterError> {
+        Self::with_config(tokens, RequesterConfig::default()).await
+    }
+
+    pub async fn with_config(tokens: impl IntoIterator<Item = impl Into<SecretToken>>, config: RequesterConfig) -> Result<Self, RequesterError> {
+        let transport = Arc::new(ReqwestTransport::from_config(&config));
+        Self::with_transport(tokens, config, transport).await
+    }
+
+    /// A pool whose clients all send through `transport`. Every profile is fetched up front, so a bad token fails
+    /// the whole pool.
+    pub async fn with_transport(tokens: impl IntoIterator<Item = impl Into<SecretToken>>, config: RequesterConfig, transport: Arc<dyn Transport>) -> Result<Self, RequesterError> {
+        let clients = future::try_join_all(tokens.into_iter().map(|token| {
+            AsyncClient::builder()
+                .with_token(token)
+                .with_config(config.clone())
+                .with_shared_transport(transport.clone())
+                .build()
+        })).await?;
+
+        if clients.is_empty() {
+            return Err(RequesterError::ValidationError("a client pool needs at least one token".to_string()));
+        }
+
+        Ok(Self { clients, next: AtomicUsize::new(0) })
+    }
+
+    pub fn clients(&self) -> &[Arc<AsyncClient>] {
+        &self.clients
+    }
+
+    pub fn len(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Always `false`: a pool can't be built without a client.
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    /// The clients in turn, for calls that work the same with any account.
+    pub fn next(&self) -> &Arc<AsyncClient> {
+        let i = self.next.fetch_add(1, Ordering::Relaxed);
+        &self.clients[i % self.clients.len()]
+    }
+
+    pub async fn by_id(&self, id: i64) -> Option<&Arc<AsyncClient>> {
+        for client in &self.clients {
+            if client.data().await.id == id {
+                return Some(client);
+            }
+        }
+        None
+    }
+
+    pub async fn by_username(&self, username: &str) -> Option<&Arc<AsyncClient>> {
+        for client in &self.clients {
+            if client.data().await.username == username {
+                return Some(client);
+            }
+        }
+        None
+    }
+
+    pub async fn fetch_character_info(&self, character_id: impl AsRef<CharacterIdRef>) -> Result<Character, RequesterError> {
+        self.next().character().fetch_character_info(character_id).await
+    }
+
+    pub async fn search_characters(&self, character_name: impl Into<&String>) -> Result<Vec<PartialCharacter>, RequesterError> {
+        self.next().character().search_characters(character_name).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn clients_share_the_transport_and_take_turns() {
+        let transport = MemoryTransport::new();
+        transport.respond_json("GET", "/chat/user/", json!({ "user": { "user": { "id": 1, "username": "someone", "account": { "name": "Someone" } } } }));
+        transport.respond_json("POST", "/chat/character/info/", json!({ "status": "OK", "character": { "external_id": "c", "participant__name": "Bot" } }));
+        let pool = ClientPool::with_transport(["first", "second"], RequesterConfig::default(), Arc::new(transport.clone())).await.unwrap();
+
+        assert_eq!(pool.len(), 2);
+        assert!(Arc::ptr_eq(pool.by_username("someone").await.unwrap(), &pool.clients()[0]));
+        assert!(pool.by_id(2).await.is_none());
+
+        for _ in 0..2 {
+            assert_eq!(pool.fetch_character_info(&"c".to_string()).await.unwrap().name, "Bot");
+        }
+        let tokens = transport.requests().iter()
+            .filter(|r| r.url.ends_with("/chat/character/info/"))
+            .map(|r| r.headers["authorization"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(tokens, ["Token first", "Token second"]);
+
+        assert!(ClientPool::with_transport(Vec::<String>::new(), RequesterConfig::default(), Arc::new(transport)).await.is_err());
+    }
+}
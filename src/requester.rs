@@ -1,83 +1,522 @@
 use futures_util::Stream;
-use reqwest::{Client, Response as ReqwestResponse, Body};
-use std::{sync::Arc, collections::HashMap};
-use tokio::{net::TcpStream, sync::RwLock};
-use tokio_tungstenite::{connect_async, tungstenite::{protocol::Message, ClientRequestBuilder}, MaybeTlsStream, WebSocketStream};
-use futures_util::{SinkExt, StreamExt};
+use reqwest::Body;
+use std::{sync::{Arc, Mutex, PoisonError}, collections::HashMap, time::Duration};
+use tokio::sync::RwLock;
+use tokio_tungstenite::tungstenite;
 use async_stream::stream;
-use http::Uri;
 use serde_json::Value;
 use thiserror::Error;
+use uuid::Uuid;
 
-#[derive(Error, Debug, PartialEq)]
+use crate::types::timestamp::Timestamp;
+
+mod cassette;
+mod limit;
+mod memory;
+mod token;
+mod transport;
+mod ws;
+
+use limit::RateLimiter;
+use ws::{Route, WsConnection};
+
+pub use cassette::{RecordingTransport, ReplayTransport};
+pub use limit::{Budget, RateLimit, RateLimitBudget};
+pub use memory::MemoryTransport;
+pub use token::{SecretToken, TokenProvider};
+pub use transport::{HttpRequest, HttpResponse, ReqwestTransport, Transport, WsFrame, WsSink, WsSource};
+pub use ws::WsHealth;
+
+#[derive(Error, Debug, Clone, PartialEq)]
 pub enum RequesterError {
-    #[error("Request failed")]
+    /// The request could not be sent or its response could not be read.
+    #[error("Request failed: {0}")]
     RequestFailed(String),
     #[error("Authentication failed")]
     AuthenticationError,
-    #[error("WebSocket connection error")]
+    /// No token was given to the client, so no authenticated request can be made.
+    #[error("No token set")]
+    MissingToken,
+    /// The server answered with a non-success status code other than 401 or 429.
+    #[error("HTTP {status}: {body}")]
+    HttpError { status: u16, body: String },
+    /// The server answered with 429 Too Many Requests.
+    #[error("Rate limited (retry after {retry_after:?})")]
+    RateLimited { retry_after: Option<Duration> },
+    /// The response was received but was not in the expected shape.
+    #[error("Could not decode response: {message}")]
+    DecodeError { message: String, payload: String },
+    /// Character.AI reported a failure, e.g. a `neo_error` frame or a response whose status isn't `OK`.
+    #[error("Character.AI API error: {0}")]
+    ApiError(String),
+    /// The arguments given to a method were rejected before any request was made.
+    #[error("Invalid input: {0}")]
+    ValidationError(String),
+    #[error("Request timed out")]
+    Timeout,
+    #[error("WebSocket error: {0}")]
     WsError(String),
+    #[error("WebSocket connection closed")]
+    WsClosed,
+    /// The websocket died without being closed, e.g. the network dropped or the server stopped answering pings.
+    /// Commands that were waiting on it fail with this error and can be sent again; the next command reconnects.
+    #[error("WebSocket connection lost: {0}")]
+    WsConnectionLost(String),
+    /// A cassette could not be read or written, or doesn't contain what a replayed session asked for.
+    #[error("Cassette error: {0}")]
+    CassetteError(String),
+    /// Every attempt allowed by the [`RetryPolicy`] failed. Holds the error of each attempt, in order.
+    #[error("Request failed after {} attempts: {}", .attempts.len(), .attempts.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    RetriesExhausted { attempts: Vec<RequesterError> },
+}
+
+impl RequesterError {
+    /// Whether the error may go away by itself, so the request is worth sending again.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::RequestFailed(_) | Self::Timeout | Self::RateLimited { .. } => true,
+            Self::HttpError { status, .. } => *status >= 500,
+            _ => false,
+        }
+    }
+
+    pub fn decode(message: impl Into<String>, payload: &Value) -> Self {
+        Self::DecodeError { message: message.into(), payload: payload.to_string() }
+    }
+
+    /// The HTTP status code associated with this error, if any.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::AuthenticationError => Some(401),
+            Self::RateLimited { .. } => Some(429),
+            Self::HttpError { status, .. } => Some(*status),
+            Self::RetriesExhausted { attempts } => attempts.last().and_then(Self::status),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for RequesterError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            Self::Timeout
+        } else {
+            Self::RequestFailed(err.to_string())
+        }
+    }
+}
+
+impl From<tungstenite::Error> for RequesterError {
+    fn from(err: tungstenite::Error) -> Self {
+        match err {
+            tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => Self::WsClosed,
+            err => Self::WsError(err.to_string()),
+        }
+    }
+}
+
+/// The Character.AI hosts a request can be addressed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Host {
+    /// The legacy REST API (`plus.character.ai`).
+    Plus,
+    /// The newer REST API (`neo.character.ai`).
+    Neo,
+    /// The tRPC API used by the web frontend (`character.ai/api/trpc`).
+    Trpc,
+    /// The CDN serving avatars and other media (`characterai.io`).
+    Media,
+}
+
+const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+/// Base URLs used by a [`Requester`].
+///
+/// Every endpoint is built from one of these, so pointing them at a local mock server or a staging proxy
+/// redirects all traffic made by the client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequesterConfig {
+    pub plus_url: String,
+    pub neo_url: String,
+    pub trpc_url: String,
+    pub ws_url: String,
+    pub media_url: String,
+    /// Sent as `user-agent` with every HTTP request and when opening the websocket.
+    pub user_agent: String,
+    /// How long an HTTP request may take, including reading the response. `None` waits forever.
+    pub request_timeout: Option<Duration>,
+    /// How long connecting to a host may take, for HTTP and the websocket alike. `None` waits forever.
+    pub connect_timeout: Option<Duration>,
+    /// How often the websocket is pinged.
+    pub ws_ping_interval: Duration,
+    /// How long to wait for any frame after a ping before the websocket is considered dead.
+    pub ws_pong_timeout: Duration,
+    /// How many times connecting the websocket is retried before giving up.
+    pub ws_reconnect_attempts: u32,
+    /// The delay before the first retry, doubling on every further retry up to 30 seconds.
+    pub ws_reconnect_backoff: Duration,
+    /// How failed HTTP requests are retried, unless overridden by [`RequestOptions::with_retry`].
+    pub retry_policy: RetryPolicy,
+    /// Client-side limits on HTTP requests per host. Hosts without an entry aren't limited.
+    pub rate_limits: HashMap<Host, RateLimit>,
+    /// Client-side limits per websocket command, keyed by command name (e.g. `create_and_generate_turn`).
+    pub ws_rate_limits: HashMap<String, RateLimit>,
+}
+
+impl Default for RequesterConfig {
+    fn default() -> Self {
+        Self {
+            plus_url: "https://plus.character.ai".to_string(),
+            neo_url: "https://neo.character.ai".to_string(),
+            trpc_url: "https://character.ai/api/trpc".to_string(),
+            ws_url: "wss://neo.character.ai/ws/".to_string(),
+            media_url: "https://characterai.io".to_string(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            request_timeout: None,
+            connect_timeout: None,
+            ws_ping_interval: Duration::from_secs(30),
+            ws_pong_timeout: Duration::from_secs(10),
+            ws_reconnect_attempts: 5,
+            ws_reconnect_backoff: Duration::from_millis(500),
+            retry_policy: RetryPolicy::default(),
+            rate_limits: HashMap::new(),
+            ws_rate_limits: HashMap::new(),
+        }
+    }
+}
+
+impl RequesterConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_plus_url(mut self, url: impl Into<String>) -> Self {
+        self.plus_url = url.into();
+        self
+    }
+
+    pub fn with_neo_url(mut self, url: impl Into<String>) -> Self {
+        self.neo_url = url.into();
+        self
+    }
+
+    pub fn with_trpc_url(mut self, url: impl Into<String>) -> Self {
+        self.trpc_url = url.into();
+        self
+    }
+
+    pub fn with_ws_url(mut self, url: impl Into<String>) -> Self {
+        self.ws_url = url.into();
+        self
+    }
+
+    pub fn with_media_url(mut self, url: impl Into<String>) -> Self {
+        self.media_url = url.into();
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_ws_heartbeat(mut self, ping_interval: Duration, pong_timeout: Duration) -> Self {
+        self.ws_ping_interval = ping_interval;
+        self.ws_pong_timeout = pong_timeout;
+        self
+    }
+
+    pub fn with_ws_reconnect(mut self, attempts: u32, backoff: Duration) -> Self {
+        self.ws_reconnect_attempts = attempts;
+        self.ws_reconnect_backoff = backoff;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    pub fn with_rate_limit(mut self, host: Host, limit: RateLimit) -> Self {
+        self.rate_limits.insert(host, limit);
+        self
+    }
+
+    pub fn with_ws_rate_limit(mut self, command: impl Into<String>, limit: RateLimit) -> Self {
+        self.ws_rate_limits.insert(command.into(), limit);
+        self
+    }
+
+    pub fn base_url(&self, host: Host) -> &str {
+        match host {
+            Host::Plus => &self.plus_url,
+            Host::Neo => &self.neo_url,
+            Host::Trpc => &self.trpc_url,
+            Host::Media => &self.media_url,
+        }
+    }
+
+    /// Joins `path` onto the base URL of `host`. `path` is expected to start with a `/`.
+    pub fn url(&self, host: Host, path: impl AsRef<str>) -> String {
+        format!("{}{}", self.base_url(host).trim_end_matches('/'), path.as_ref())
+    }
+
+    /// The host whose base URL `url` starts with, preferring the longest match (tRPC lives under the main site).
+    pub fn host_of(&self, url: &str) -> Option<Host> {
+        [Host::Plus, Host::Neo, Host::Trpc, Host::Media].into_iter()
+            .filter(|host| url.starts_with(self.base_url(*host).trim_end_matches('/')))
+            .max_by_key(|host| self.base_url(*host).len())
+    }
+}
+
+/// How a failed HTTP request is retried.
+///
+/// Connection errors, timeouts, 5xx responses and 429s are retried, waiting `initial_backoff` (doubling every
+/// attempt, up to `max_backoff`, with random jitter) or for as long as the `Retry-After` header asks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// The total number of times a request is sent, including the first one.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    /// The longest delay between attempts, including one asked for by a `Retry-After` header.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        Self { max_attempts, initial_backoff, max_backoff }
+    }
+
+    /// Sends every request exactly once.
+    pub fn none() -> Self {
+        Self::new(1, Duration::ZERO, Duration::ZERO)
+    }
+
+    /// The delay before retrying after `attempt` (starting at 1) failed with `err`.
+    fn backoff(&self, attempt: u32, err: &RequesterError) -> Duration {
+        if let RequesterError::RateLimited { retry_after: Some(retry_after) } = err {
+            return (*retry_after).min(self.max_backoff);
+        }
+
+        let backoff = self.initial_backoff.saturating_mul(2u32.saturating_pow(attempt - 1)).min(self.max_backoff);
+        backoff.mul_f64(rand::random_range(0.5..=1.0))
+    }
+}
+
+/// The delay a `Retry-After` header asks for, given either as seconds or as an HTTP date such as
+/// `Wed, 21 Oct 2015 07:28:00 GMT`. A date in the past means no delay.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    // Only the IMF-fixdate form is accepted, as RFC 9110 requires senders to use it.
+    let (_, date) = value.split_once(", ")?;
+    let [day, month, year, time, "GMT"] = date.split(' ').collect::<Vec<_>>()[..] else { return None };
+    let month = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"].iter().position(|m| *m == month)? + 1;
+    let date = Timestamp::parse_rfc3339(&format!("{}-{:02}-{}T{}Z", year, month, day, time))?;
+    Some(date.duration_since(Timestamp::now()).unwrap_or_default())
 }
 
 pub struct RequestOptions {
     pub method: String,
     pub headers: HashMap<String, String>,
     pub body: Option<Body>,
+    /// Overrides the configured [`RetryPolicy`] for this request. Setting it also marks the request as safe to
+    /// retry, which is otherwise only assumed for `GET`, `PUT` and `DELETE`.
+    pub retry: Option<RetryPolicy>,
 }
 
 impl RequestOptions {
     pub fn new(method: impl Into<String>, headers: HashMap<String, String>, body: Option<Body>) -> Self {
-        Self { method: method.into(), headers, body }
+        Self { method: method.into(), headers, body, retry: None }
+    }
+
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Requester {
-    client: Client,
-    ws_client: Arc<RwLock<Option<WebSocketStream<MaybeTlsStream<TcpStream>>>>>,
+    transport: Arc<dyn Transport>,
+    config: RequesterConfig,
+    ws_client: Arc<RwLock<Option<Arc<WsConnection>>>>,
+    ws_history: Arc<Mutex<WsHealth>>,
+    limiter: Arc<RateLimiter>,
+    token_provider: Option<Arc<dyn TokenProvider>>,
+}
+
+impl Default for Requester {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Requester {
     pub fn new() -> Self {
-        let client = Client::builder()
-            .build()
-            .expect("Failed to build client");
+        Self::with_config(RequesterConfig::default())
+    }
+
+    pub fn with_config(config: RequesterConfig) -> Self {
+        let transport = ReqwestTransport::from_config(&config);
+        Self::with_transport(config, transport)
+    }
+
+    /// A requester that talks to Character.AI and records the session to a cassette at `path`.
+    pub fn recording(config: RequesterConfig, path: impl Into<std::path::PathBuf>) -> Self {
+        let transport = RecordingTransport::new(ReqwestTransport::from_config(&config), path);
+        Self::with_transport(config, transport)
+    }
 
+    /// A requester that serves a cassette written by [`Requester::recording`] instead of using the network.
+    pub fn replaying(config: RequesterConfig, path: impl AsRef<std::path::Path>) -> Result<Self, RequesterError> {
+        Ok(Self::with_transport(config, ReplayTransport::load(path)?))
+    }
+
+    /// A requester that sends everything through `transport`, e.g. a [`MemoryTransport`] in tests.
+    pub fn with_transport(config: RequesterConfig, transport: impl Transport + 'static) -> Self {
+        Self::with_shared_transport(config, Arc::new(transport))
+    }
+
+    /// A requester using a transport shared with other requesters, so they reuse the same connection pool.
+    pub fn with_shared_transport(config: RequesterConfig, transport: Arc<dyn Transport>) -> Self {
         Self {
-            client,
+            transport,
+            limiter: Arc::new(RateLimiter::new(&config.rate_limits, &config.ws_rate_limits)),
+            config,
             ws_client: Arc::new(RwLock::new(None)),
+            ws_history: Arc::new(Mutex::new(WsHealth::default())),
+            token_provider: None,
         }
     }
 
+    /// Refreshes rejected tokens with `provider`, retrying the rejected request or websocket connection once.
+    pub fn with_token_provider(mut self, provider: Arc<dyn TokenProvider>) -> Self {
+        self.token_provider = Some(provider);
+        self
+    }
+
+    pub fn token_provider(&self) -> Option<&Arc<dyn TokenProvider>> {
+        self.token_provider.as_ref()
+    }
+
+    pub fn config(&self) -> &RequesterConfig {
+        &self.config
+    }
+
+    pub fn url(&self, host: Host, path: impl AsRef<str>) -> String {
+        self.config.url(host, path)
+    }
+
+    /// What's left of every configured rate limit.
+    pub fn rate_limit_budget(&self) -> RateLimitBudget {
+        self.limiter.budget()
+    }
+
     pub async fn request_resp_async(
         &self,
         url: impl Into<String>,
         options: RequestOptions,
-    ) -> Result<ReqwestResponse, RequesterError> {
+    ) -> Result<HttpResponse, RequesterError> {
         let url = url.into();
-        let mut req = match options.method.as_str() {
-            "GET" => self.client.get(url),
-            "POST" => self.client.post(url),
-            "PUT" => self.client.put(url),
-            "PATCH" => self.client.patch(url),
-            "DELETE" => self.client.delete(url),
+        let idempotent = match options.method.as_str() {
+            "GET" | "PUT" | "DELETE" => true,
+            "POST" | "PATCH" => false,
             _ => return Err(RequesterError::RequestFailed("Invalid method. Please use one of GET, POST, PUT, PATCH, or DELETE.".to_string())),
         };
-
-        for (key, value) in options.headers.iter() {
-            req = req.header(key, value);
+        let body = match options.body {
+            Some(body) => Some(body.as_bytes().map(Vec::from).ok_or(RequesterError::ValidationError("streaming request bodies are not supported".to_string()))?),
+            None => None,
+        };
+        let policy = match options.retry {
+            Some(policy) => policy,
+            None if idempotent => self.config.retry_policy.clone(),
+            None => RetryPolicy::none(),
+        };
+        let mut headers = options.headers;
+        if !headers.keys().any(|name| name.eq_ignore_ascii_case("user-agent")) {
+            headers.insert("user-agent".to_string(), self.config.user_agent.clone());
         }
+        let mut request = HttpRequest { method: options.method, url, headers, body };
+
+        let host = self.config.host_of(&request.url);
+        let mut attempts = vec![];
+        let mut refreshed = false;
+        loop {
+            if let Some(host) = host {
+                self.limiter.acquire_host(host).await;
+            }
+
+            let err = match self.send_once(request.clone()).await {
+                Ok(res) => return Ok(res),
+                Err(err) => err,
+            };
+
+            if err == RequesterError::AuthenticationError
+                && !refreshed
+                && let Some(provider) = &self.token_provider
+                && let Some(rejected) = request.headers.get("authorization").and_then(|v| v.strip_prefix("Token "))
+            {
+                let token = provider.refresh(&SecretToken::new(rejected)).await?;
+                request.headers.insert("authorization".to_string(), token.authorization());
+                refreshed = true;
+                continue;
+            }
+
+            let attempt = attempts.len() as u32 + 1;
+            if !err.is_transient() || attempt >= policy.max_attempts {
+                if attempts.is_empty() {
+                    return Err(err);
+                }
+                attempts.push(err);
+                return Err(RequesterError::RetriesExhausted { attempts });
+            }
 
-        if let Some(body) = options.body {
-            req = req.body(body);
+            tokio::time::sleep(policy.backoff(attempt, &err)).await;
+            attempts.push(err);
         }
+    }
 
-        let res: ReqwestResponse = req.send().await.map_err(|err| RequesterError::RequestFailed(err.to_string()))?;
+    async fn send_once(&self, request: HttpRequest) -> Result<HttpResponse, RequesterError> {
+        let res = match self.config.request_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.transport.request(request)).await.map_err(|_| RequesterError::Timeout)??,
+            None => self.transport.request(request).await?,
+        };
 
-        if res.status() == 401 {
+        if res.status == 401 {
             return Err(RequesterError::AuthenticationError);
         }
-        
+        if res.status == 429 {
+            let retry_after = res.header("retry-after").and_then(parse_retry_after);
+            return Err(RequesterError::RateLimited { retry_after });
+        }
+        if !(200..300).contains(&res.status) {
+            return Err(RequesterError::HttpError { status: res.status, body: res.text() });
+        }
+
         Ok(res)
     }
 
@@ -86,81 +525,204 @@ impl Requester {
         url: impl Into<String>,
         options: RequestOptions,
     ) -> Result<Value, RequesterError> {
-        let text = self.request_resp_async(url, options).await?.text().await.map_err(|err| RequesterError::RequestFailed(err.to_string()))?;
-        let json: Value = serde_json::from_str(&text).map_err(|err| RequesterError::RequestFailed(err.to_string()))?;
-        Ok(json)
+        let res = self.request_resp_async(url, options).await?;
+        serde_json::from_slice(&res.body).map_err(|err| RequesterError::DecodeError { message: err.to_string(), payload: res.text() })
     }
 
-    pub async fn ws_connect(&self, token: impl Into<String>) -> Result<(), RequesterError> {
-        let uri: Uri = "wss://neo.character.ai/ws/".parse().unwrap();
-        let builder = ClientRequestBuilder::new(uri)
-            .with_header("user-agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
-            .with_header("Cookie", format!("HTTP_AUTHORIZATION=\"Token {}\"", token.into()));
-        let (ws_stream, _) = connect_async(builder)
-            .await
-            .map_err(|_| RequesterError::WsError("could not connect to websocket url wss://neo.character.ai/ws/".to_string()))?;
+    async fn ws_open(&self, token: &SecretToken) -> Result<WsConnection, RequesterError> {
+        let headers = HashMap::from([
+            ("user-agent".to_string(), self.config.user_agent.clone()),
+            ("Cookie".to_string(), token.cookie()),
+        ]);
+        let connect = self.transport.connect_ws(&self.config.ws_url, &headers);
+        let ws = match self.config.connect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, connect).await.map_err(|_| RequesterError::Timeout)??,
+            None => connect.await?,
+        };
 
-        let mut guard = self.ws_client.write().await;
-        *guard = Some(ws_stream);
+        Ok(WsConnection::spawn(ws, self.config.ws_ping_interval, self.config.ws_pong_timeout))
+    }
+
+    pub async fn ws_connect(&self, token: impl Into<SecretToken>) -> Result<(), RequesterError> {
+        let ws = self.ws_open(&token.into()).await?;
+        let old = self.ws_client.write().await.replace(Arc::new(ws));
+        if let Some(old) = old.and_then(Arc::into_inner) {
+            old.close().await;
+        }
 
         Ok(())
     }
 
-    pub async fn ws_close(&self) {
+    /// The open connection. If there isn't one, or the last one died, a new one is opened, retrying with
+    /// exponential backoff.
+    async fn ws_connection(&self, mut token: SecretToken) -> Result<Arc<WsConnection>, RequesterError> {
+        if let Some(ws) = self.ws_client.read().await.as_ref().filter(|ws| !ws.is_closed()) {
+            return Ok(Arc::clone(ws));
+        }
+
         let mut guard = self.ws_client.write().await;
-        if let Some(mut ws) = guard.take() {
-            let _ = ws.close(None).await;
+        if let Some(ws) = guard.as_ref().filter(|ws| !ws.is_closed()) {
+            return Ok(Arc::clone(ws));
+        }
+
+        if let Some(dead) = guard.take() {
+            let mut history = self.ws_history.lock().unwrap_or_else(PoisonError::into_inner);
+            history.reconnects += 1;
+            history.last_error = dead.health().last_error;
         }
+
+        let mut backoff = self.config.ws_reconnect_backoff;
+        let mut attempt = 0;
+        let mut refreshed = false;
+        let ws = loop {
+            match self.ws_open(&token).await {
+                Ok(ws) => break Arc::new(ws),
+                Err(err @ RequesterError::AuthenticationError) => match self.token_provider.as_ref().filter(|_| !refreshed) {
+                    Some(provider) => {
+                        token = provider.refresh(&token).await?;
+                        refreshed = true;
+                    }
+                    None => return Err(err),
+                },
+                Err(err) if attempt >= self.config.ws_reconnect_attempts => return Err(err),
+                Err(_) => {
+                    attempt += 1;
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(Duration::from_secs(30));
+                }
+            }
+        };
+        *guard = Some(Arc::clone(&ws));
+
+        Ok(ws)
     }
 
-    pub async fn ws_send(&self, message: &Value) -> Result<(), RequesterError> {
-        let mut guard = self.ws_client.write().await;
+    /// The state of the websocket connection, for monitoring long-running clients.
+    pub async fn ws_health(&self) -> WsHealth {
+        let history = self.ws_history.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let current = self.ws_client.read().await.as_ref().map(|ws| ws.health()).unwrap_or_default();
 
-        if let Some(ws) = guard.as_mut() {
-            let text = serde_json::to_string(message).unwrap();
-            ws.send(Message::Text(text.into())).await.map_err(|_| RequesterError::WsError("websocket.send failed".to_string()))?;
-            Ok(())
-        } else {
-            Err(RequesterError::WsError("could not obtain websocket".to_string()))
+        WsHealth {
+            reconnects: history.reconnects,
+            last_error: current.last_error.or(history.last_error),
+            ..current
         }
     }
 
-    pub async fn ws_receive(&self) -> impl Stream<Item = Result<Value, RequesterError>> {
-        let ws_client = Arc::clone(&self.ws_client);
+    pub async fn ws_close(&self) {
+        let ws = self.ws_client.write().await.take();
+        if let Some(ws) = ws.and_then(Arc::into_inner) {
+            ws.close().await;
+        }
+    }
+
+    pub async fn ws_send(&self, message: &Value) -> Result<(), RequesterError> {
+        let ws = self.ws_client.read().await.clone().ok_or(RequesterError::WsClosed)?;
+        self.limiter.acquire_ws_command(message["command"].as_str().unwrap_or_default()).await;
+        ws.send(message).await
+    }
+
+    /// Every incoming frame that isn't an answer to a command sent with [`Requester::ws_send_and_receive`].
+    pub async fn ws_receive(&self) -> impl Stream<Item = Result<Value, RequesterError>> + use<> {
+        let subscription = self.ws_client.read().await.as_ref().and_then(|ws| ws.subscribe(Route::default()).ok());
 
         stream! {
-            let mut guard = ws_client.write().await;
-            if let Some(ws) = guard.as_mut() {
-                let (_, mut read) = ws.split();
-
-                while let Some(msg) = read.next().await {
-                    match msg {
-                        Ok(Message::Text(text)) => {
-                            match serde_json::from_str::<Value>(&text) {
-                                Ok(val) => {
-                                    yield Ok(val);
-                                }
-                                Err(_) => {
-                                    yield Err(RequesterError::WsError("invalid JSON received".to_string()));
-                                }
-                            }
-                        }
-                        Ok(_) => continue,
-                        Err(e) => {
-                            yield Err(RequesterError::WsError(format!("WebSocket read error: {}", e)));
-                            break;
-                        }
-                    }
+            if let Some(subscription) = subscription {
+                for await frame in subscription.into_stream() {
+                    yield frame;
                 }
             }
         }
     }
 
-    pub async fn ws_send_and_receive(&self, message: &Value, token: String) -> Result<impl Stream<Item = Result<Value, RequesterError>>, RequesterError> {
-        if self.ws_client.read().await.is_none() {
-            self.ws_connect(token).await?
+    /// Sends a neo command and streams back only the frames that answer it, so commands can run concurrently.
+    ///
+    /// A `request_id` is added to the command if it doesn't have one.
+    pub async fn ws_send_and_receive(&self, message: &Value, token: SecretToken) -> Result<impl Stream<Item = Result<Value, RequesterError>> + use<>, RequesterError> {
+        let mut message = message.clone();
+        if message.get("request_id").is_none() {
+            message["request_id"] = Value::String(Uuid::new_v4().to_string());
         }
-        self.ws_send(message).await?;
-        Ok(self.ws_receive().await)
+
+        let ws = self.ws_connection(token).await?;
+        self.limiter.acquire_ws_command(message["command"].as_str().unwrap_or_default()).await;
+        let subscription = ws.subscribe(Route::of(&message))?;
+        ws.send(&message).await?;
+
+        Ok(subscription.into_stream())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+
+    use super::*;
+
+    /// Serves one canned HTTP response per connection, in order.
+    async fn serve(responses: Vec<&'static str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = [0; 4096];
+                let _ = socket.read(&mut buf).await;
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        url
+    }
+
+    const BAD_GATEWAY: &str = "HTTP/1.1 502 Bad Gateway\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+    const TOO_MANY: &str = "HTTP/1.1 429 Too Many Requests\r\nretry-after: 0\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+    const OK: &str = "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{}";
+
+    #[tokio::test]
+    async fn idempotent_requests_are_retried() {
+        let url = serve(vec![BAD_GATEWAY, TOO_MANY, OK]).await;
+        let policy = RetryPolicy::new(3, Duration::from_millis(1), Duration::from_millis(1));
+        let requester = Requester::with_config(RequesterConfig::new().with_retry_policy(policy));
+
+        let json = requester.request_async(url, RequestOptions::new("GET", HashMap::new(), None)).await;
+        assert_eq!(json, Ok(serde_json::json!({})));
+    }
+
+    #[tokio::test]
+    async fn retry_after_is_honored() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        assert!(parse_retry_after("Wed, 21 Oct 2099 07:28:00 GMT").unwrap() > Duration::from_secs(60 * 60 * 24 * 365));
+        assert_eq!(parse_retry_after("soon"), None);
+
+        let transport = MemoryTransport::new();
+        transport.respond("GET", "/", HttpResponse::new(429, "").with_header("retry-after", "7"));
+        let requester = Requester::with_transport(RequesterConfig::new().with_retry_policy(RetryPolicy::none()), transport);
+        let err = requester.request_async("https://plus.character.ai/", RequestOptions::new("GET", HashMap::new(), None)).await.unwrap_err();
+        assert_eq!(err, RequesterError::RateLimited { retry_after: Some(Duration::from_secs(7)) });
+        assert_eq!(RetryPolicy::default().backoff(1, &err), Duration::from_secs(7));
+
+        let err = RequesterError::RateLimited { retry_after: parse_retry_after("86400") };
+        assert_eq!(RetryPolicy::default().backoff(1, &err), RetryPolicy::default().max_backoff);
+    }
+
+    #[tokio::test]
+    async fn retries_report_every_attempt() {
+        let url = serve(vec![BAD_GATEWAY, BAD_GATEWAY, BAD_GATEWAY]).await;
+        let requester = Requester::new();
+
+        // POST isn't retried unless asked to.
+        let err = requester.request_async(&url, RequestOptions::new("POST", HashMap::new(), Some("{}".into()))).await.unwrap_err();
+        assert_eq!(err.status(), Some(502));
+        assert!(!matches!(err, RequesterError::RetriesExhausted { .. }));
+
+        let options = RequestOptions::new("POST", HashMap::new(), Some("{}".into()))
+            .with_retry(RetryPolicy::new(2, Duration::from_millis(1), Duration::from_millis(1)));
+        match requester.request_async(&url, options).await {
+            Err(RequesterError::RetriesExhausted { attempts }) => assert_eq!(attempts.len(), 2),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::{collections::{HashMap, VecDeque}, fmt, fs::File, io::Write, path::{Path, PathBuf}, sync::{Arc, Mutex, MutexGuard, PoisonError}};
+use async_stream::stream;
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose};
+use futures_util::{future, sink, SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use super::{transport::*, RequesterError};
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn cassette_error(path: &Path, err: impl fmt::Display) -> RequesterError {
+    RequesterError::CassetteError(format!("{}: {}", path.display(), err))
+}
+
+fn encode_body(body: &[u8]) -> Value {
+    match std::str::from_utf8(body) {
+        Ok(text) => json!({ "text": text }),
+        Err(_) => json!({ "base64": general_purpose::STANDARD.encode(body) }),
+    }
+}
+
+fn decode_body(body: &Value) -> Vec<u8> {
+    match (body["text"].as_str(), body["base64"].as_str()) {
+        (Some(text), _) => text.as_bytes().to_vec(),
+        (None, Some(data)) => general_purpose::STANDARD.decode(data).unwrap_or_default(),
+        (None, None) => vec![],
+    }
+}
+
+/// A websocket event for a text frame: the JSON itself under `frame` if it is JSON, so cassettes stay readable,
+/// or the raw text under `text`.
+fn frame_event(kind: &str, text: &str) -> Value {
+    match serde_json::from_str::<Value>(text) {
+        Ok(frame) => json!({ "type": kind, "frame": frame }),
+        Err(_) => json!({ "type": kind, "text": text }),
+    }
+}
+
+/// A [`Transport`] that passes everything through to another transport and writes every HTTP exchange and
+/// websocket text frame to a cassette file, with the token redacted. Interactions are appended to the file one
+/// JSON line at a time, so it's complete even if the process is killed.
+///
+/// A failure to record an HTTP exchange fails the request. A failure to record a websocket frame doesn't touch the
+/// live session; it is kept for [`RecordingTransport::take_error`] instead.
+#[derive(Clone)]
+pub struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    path: PathBuf,
+    file: Arc<Mutex<Option<File>>>,
+    error: Arc<Mutex<Option<RequesterError>>>,
+}
+
+impl fmt::Debug for RecordingTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordingTransport").field("inner", &self.inner).field("path", &self.path).finish()
+    }
+}
+
+impl RecordingTransport {
+    pub fn new(inner: impl Transport + 'static, path: impl Into<PathBuf>) -> Self {
+        Self { inner: Arc::new(inner), path: path.into(), file: Arc::new(Mutex::new(None)), error: Arc::new(Mutex::new(None)) }
+    }
+
+    /// The first error hit while recording a websocket frame since the last call, if any.
+    pub fn take_error(&self) -> Option<RequesterError> {
+        lock(&self.error).take()
+    }
+
+    /// Appends `interaction` to the cassette. The file is truncated when the first interaction is recorded.
+    fn record(&self, interaction: Value) -> Result<(), RequesterError> {
+        let mut file = lock(&self.file);
+        let file = match &mut *file {
+            Some(file) => file,
+            None => file.insert(File::create(&self.path).map_err(|err| cassette_error(&self.path, err))?),
+        };
+
+        let mut line = serde_json::to_vec(&interaction).map_err(|err| cassette_error(&self.path, err))?;
+        line.push(b'\n');
+        file.write_all(&line).map_err(|err| cassette_error(&self.path, err))
+    }
+
+    /// Records a websocket frame, keeping the error rather than failing the frame.
+    fn record_frame(&self, interaction: Value) {
+        if let Err(err) = self.record(interaction) {
+            lock(&self.error).get_or_insert(err);
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for RecordingTransport {
+    async fn request(&self, request: HttpRequest) -> Result<HttpResponse, RequesterError> {
+        let recorded_request = json!({
+            "method": request.method,
+            "url": request.url,
+            "headers": redact(&request.headers),
+            "body": request.body.as_deref().map(encode_body),
+        });
+        let response = self.inner.request(request).await?;
+
+        self.record(json!({
+            "type": "http",
+            "request": recorded_request,
+            "response": {
+                "status": response.status,
+                "headers": response.headers,
+                "body": encode_body(&response.body),
+            },
+        }))?;
+
+        Ok(response)
+    }
+
+    async fn connect_ws(&self, url: &str, headers: &HashMap<String, String>) -> Result<(WsSink, WsSource), RequesterError> {
+        let (sink, source) = self.inner.connect_ws(url, headers).await?;
+        self.record(json!({ "type": "ws_connect", "url": url }))?;
+
+        let recorder = self.clone();
+        let sink = sink.with(move |frame: WsFrame| {
+            if let WsFrame::Text(text) = &frame {
+                recorder.record_frame(frame_event("ws_send", text));
+            }
+            future::ready(Ok::<_, RequesterError>(frame))
+        });
+
+        let recorder = self.clone();
+        let source = source.inspect(move |frame| {
+            if let Ok(WsFrame::Text(text)) = frame {
+                recorder.record_frame(frame_event("ws_receive", text));
+            }
+        });
+
+        Ok((Box::pin(sink), Box::pin(source)))
+    }
+}
+
+#[derive(Debug, Default)]
+struct Replay {
+    /// HTTP exchanges that haven't been served yet, in recorded order.
+    http: Vec<Value>,
+    /// Websocket sends and receives that haven't been replayed yet.
+    ws: VecDeque<Value>,
+}
+
+impl Replay {
+    /// The frames the server sent in answer to `command`, with the recorded request id swapped for the live one.
+    fn answer(&mut self, command: &Value) -> Result<Vec<WsFrame>, RequesterError> {
+        let mut frames = self.take_receives();
+
+        let recorded = loop {
+            match self.ws.pop_front() {
+                Some(event) if event["type"] == "ws_send" => break event["frame"].clone(),
+                Some(_) => continue,
+                None => return Err(RequesterError::CassetteError(format!("no recorded websocket command left for {}", command["command"]))),
+            }
+        };
+        if recorded["command"] != command["command"] {
+            return Err(RequesterError::CassetteError(format!("expected websocket command {} but {} was sent", recorded["command"], command["command"])));
+        }
+
+        let (recorded_id, live_id) = (&recorded["request_id"], &command["request_id"]);
+        frames.extend(self.take_receives().into_iter().map(|mut frame| {
+            if let WsFrame::Text(text) = &mut frame
+                && !recorded_id.is_null()
+                && let Ok(mut json) = serde_json::from_str::<Value>(text)
+                && json["request_id"] == *recorded_id
+            {
+                json["request_id"] = live_id.clone();
+                *text = json.to_string();
+            }
+            frame
+        }));
+
+        Ok(frames)
+    }
+
+    fn take_receives(&mut self) -> Vec<WsFrame> {
+        let mut frames = vec![];
+        while self.ws.front().is_some_and(|event| event["type"] == "ws_receive") {
+            let event = self.ws.pop_front().unwrap();
+            frames.push(WsFrame::Text(event["text"].as_str().map_or_else(|| event["frame"].to_string(), String::from)));
+        }
+        frames
+    }
+}
+
+/// A [`Transport`] that serves a cassette written by [`RecordingTransport`] without touching the network.
+///
+/// HTTP requests are answered by the first unused recording with the same method and URL. Websocket commands must
+/// be sent in the recorded order; each is answered with the frames recorded after it, with their `request_id`
+/// rewritten to the one of the live command.
+#[derive(Debug, Clone)]
+pub struct ReplayTransport {
+    replay: Arc<Mutex<Replay>>,
+}
+
+impl ReplayTransport {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RequesterError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|err| cassette_error(path, err))?;
+        let interactions = text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str::<Value>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| cassette_error(path, err))?;
+
+        let (http, ws) = interactions.into_iter().partition(|i| i["type"] == "http");
+        Ok(Self { replay: Arc::new(Mutex::new(Replay { http, ws: ws.into_iter().filter(|i| i["type"] != "ws_connect").collect() })) })
+    }
+}
+
+#[async_trait]
+impl Transport for ReplayTransport {
+    async fn request(&self, request: HttpRequest) -> Result<HttpResponse, RequesterError> {
+        let mut replay = lock(&self.replay);
+        let i = replay.http.iter()
+            .position(|i| i["request"]["method"] == request.method && i["request"]["url"] == request.url)
+            .ok_or_else(|| RequesterError::CassetteError(format!("no recorded response for {} {}", request.method, request.url)))?;
+        let response = replay.http.remove(i).get_mut("response").map(Value::take).unwrap_or_default();
+
+        Ok(HttpResponse {
+            status: response["status"].as_u64().unwrap_or(200) as u16,
+            headers: response["headers"].as_object().map(|h| h.iter().filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string()))).collect()).unwrap_or_default(),
+            body: decode_body(&response["body"]),
+        })
+    }
+
+    async fn connect_ws(&self, _url: &str, _headers: &HashMap<String, String>) -> Result<(WsSink, WsSource), RequesterError> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let sink = sink::unfold((Arc::clone(&self.replay), tx), |(replay, tx), frame: WsFrame| async move {
+            let frames = match frame {
+                WsFrame::Text(text) => {
+                    let command = serde_json::from_str::<Value>(&text).map_err(|err| RequesterError::WsError(err.to_string()))?;
+                    lock(&replay).answer(&command)?
+                }
+                WsFrame::Ping(data) => vec![WsFrame::Pong(data)],
+                WsFrame::Close => vec![WsFrame::Close],
+                _ => vec![],
+            };
+            for frame in frames {
+                let _ = tx.send(frame);
+            }
+
+            Ok::<_, RequesterError>((replay, tx))
+        });
+        let source = stream! {
+            while let Some(frame) = rx.recv().await {
+                yield Ok(frame);
+            }
+        };
+
+        Ok((Box::pin(sink), Box::pin(source)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{client::AsyncClient, requester::{MemoryTransport, Requester, RequesterConfig}};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn replays_a_recorded_session() {
+        let path = std::env::temp_dir().join(format!("rustic_ai-cassette-{}.json", uuid::Uuid::new_v4()));
+
+        let live = MemoryTransport::new();
+        live.respond_json("GET", "/chat/user/", json!({ "user": { "user": { "id": 1, "username": "someone", "account": { "name": "Someone" } } } }));
+        live.on_ws_command(|command| vec![json!({
+            "command": "update_turn",
+            "request_id": command["request_id"],
+            "turn": {
+                "turn_key": { "chat_id": "chat", "turn_id": "reply" },
+                "author": { "author_id": "2", "name": "Bot" },
+                "primary_candidate_id": "c",
+                "candidates": [{ "candidate_id": "c", "raw_content": "Hello!", "is_final": true }]
+            }
+        })]);
+        let requester = Requester::with_transport(RequesterConfig::default(), RecordingTransport::new(live, &path));
+        let client = AsyncClient::with_requester(Some("secret-token".to_string()), requester).await;
+        let recorded = client.chat().send_message(&"character".to_string(), &"chat".to_string(), &"Hi".to_string()).await.unwrap();
+
+        let cassette = std::fs::read_to_string(&path).unwrap();
+        assert!(!cassette.contains("secret-token"));
+        assert_eq!(cassette.lines().count(), 4);
+
+        let requester = Requester::with_transport(RequesterConfig::default(), ReplayTransport::load(&path).unwrap());
+        let client = AsyncClient::with_requester(Some("another-token".to_string()), requester).await;
+        assert_eq!(client.data().await.username, "someone");
+        let replayed = client.chat().send_message(&"character".to_string(), &"chat".to_string(), &"Hi".to_string()).await.unwrap();
+        assert_eq!(replayed, recorded);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,158 @@
+use std::{collections::HashMap, sync::{Mutex, PoisonError}, time::{Duration, Instant}};
+
+use super::{Host, RequesterError};
+
+/// A token bucket: up to `burst` requests at once, refilled at `per_second` requests per second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    burst: u32,
+    per_second: f64,
+}
+
+impl RateLimit {
+    /// Fails unless `burst` is at least 1 and `per_second` is positive, as anything else would never let a
+    /// request through.
+    pub fn new(burst: u32, per_second: f64) -> Result<Self, RequesterError> {
+        if burst == 0 {
+            return Err(RequesterError::ValidationError("a rate limit needs a burst of at least 1".to_string()));
+        }
+        if !(per_second > 0.0 && per_second.is_finite()) {
+            return Err(RequesterError::ValidationError(format!("a rate limit needs a positive, finite rate (is {})", per_second)));
+        }
+        Ok(Self { burst, per_second })
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    pub fn per_second(&self) -> f64 {
+        self.per_second
+    }
+}
+
+/// How much of a [`RateLimit`] is left right now.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Budget {
+    /// Requests that can be made immediately, fractional while the bucket refills.
+    pub available: f64,
+    pub burst: u32,
+}
+
+/// The current budget of every configured limit, see [`crate::requester::Requester::rate_limit_budget`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateLimitBudget {
+    pub hosts: HashMap<Host, Budget>,
+    pub ws_commands: HashMap<String, Budget>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    limit: RateLimit,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit) -> Self {
+        Self { limit, tokens: limit.burst as f64, refilled: Instant::now() }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let earned = (now - self.refilled).as_secs_f64() * self.limit.per_second;
+        self.tokens = (self.tokens + earned).min(self.limit.burst as f64);
+        self.refilled = now;
+    }
+
+    /// Takes a token, or says how long until one is available.
+    fn take(&mut self) -> Result<(), Duration> {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::try_from_secs_f64((1.0 - self.tokens) / self.limit.per_second).unwrap_or(Duration::MAX))
+        }
+    }
+
+    fn budget(&mut self) -> Budget {
+        self.refill();
+        Budget { available: self.tokens, burst: self.limit.burst }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct RateLimiter {
+    hosts: HashMap<Host, Mutex<Bucket>>,
+    ws_commands: HashMap<String, Mutex<Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(hosts: &HashMap<Host, RateLimit>, ws_commands: &HashMap<String, RateLimit>) -> Self {
+        Self {
+            hosts: hosts.iter().map(|(host, limit)| (*host, Mutex::new(Bucket::new(*limit)))).collect(),
+            ws_commands: ws_commands.iter().map(|(command, limit)| (command.clone(), Mutex::new(Bucket::new(*limit)))).collect(),
+        }
+    }
+
+    async fn acquire(bucket: Option<&Mutex<Bucket>>) {
+        let Some(bucket) = bucket else { return };
+        loop {
+            let wait = bucket.lock().unwrap_or_else(PoisonError::into_inner).take();
+            match wait {
+                Ok(()) => return,
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+
+    /// Waits until a request to `host` is allowed.
+    pub async fn acquire_host(&self, host: Host) {
+        Self::acquire(self.hosts.get(&host)).await
+    }
+
+    /// Waits until the websocket command `command` is allowed.
+    pub async fn acquire_ws_command(&self, command: &str) {
+        Self::acquire(self.ws_commands.get(command)).await
+    }
+
+    pub fn budget(&self) -> RateLimitBudget {
+        let budget = |bucket: &Mutex<Bucket>| bucket.lock().unwrap_or_else(PoisonError::into_inner).budget();
+
+        RateLimitBudget {
+            hosts: self.hosts.iter().map(|(host, bucket)| (*host, budget(bucket))).collect(),
+            ws_commands: self.ws_commands.iter().map(|(command, bucket)| (command.clone(), budget(bucket))).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn waits_for_the_bucket_to_refill() {
+        let limiter = RateLimiter::new(&HashMap::from([(Host::Neo, RateLimit::new(2, 20.0).unwrap())]), &HashMap::new());
+
+        let start = Instant::now();
+        for _ in 0..4 {
+            limiter.acquire_host(Host::Neo).await;
+        }
+        // Two requests come out of the burst, the other two wait 50ms each.
+        assert!(start.elapsed() >= Duration::from_millis(90));
+
+        // Hosts without a limit never wait.
+        limiter.acquire_host(Host::Plus).await;
+        assert!(limiter.budget().hosts[&Host::Neo].available < 1.0);
+        assert!(!limiter.budget().hosts.contains_key(&Host::Plus));
+    }
+
+    #[test]
+    fn limits_that_never_allow_a_request_are_rejected() {
+        assert!(RateLimit::new(0, 1.0).is_err());
+        assert!(RateLimit::new(1, 0.0).is_err());
+        assert!(RateLimit::new(1, f64::NAN).is_err());
+        assert_eq!(RateLimit::new(1, 0.5).unwrap().burst(), 1);
+    }
+}
@@ -0,0 +1,127 @@
+use std::{collections::HashMap, fmt, sync::{Arc, Mutex, MutexGuard, PoisonError}};
+use async_stream::stream;
+use async_trait::async_trait;
+use futures_util::sink;
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use super::{transport::*, RequesterError};
+
+type WsHandler = Arc<dyn Fn(&Value) -> Vec<Value> + Send + Sync>;
+
+#[derive(Default)]
+struct State {
+    routes: Vec<(String, String, HttpResponse)>,
+    requests: Vec<HttpRequest>,
+    ws_handler: Option<WsHandler>,
+    ws_commands: Vec<Value>,
+}
+
+/// A [`Transport`] that never touches the network, answering from responses registered up front.
+///
+/// HTTP requests are matched on method and path; websocket commands are answered by a handler that returns the
+/// frames the server would send back. Everything sent is recorded for assertions.
+///
+/// Unmatched HTTP requests get a 404. Websocket pings are answered with pongs.
+#[derive(Clone, Default)]
+pub struct MemoryTransport {
+    state: Arc<Mutex<State>>,
+}
+
+impl fmt::Debug for MemoryTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state();
+        f.debug_struct("MemoryTransport")
+            .field("routes", &state.routes.len())
+            .field("requests", &state.requests.len())
+            .field("ws_commands", &state.ws_commands.len())
+            .finish()
+    }
+}
+
+/// The path and query of `url`, e.g. `/chat/user/?x=1` for `https://plus.character.ai/chat/user/?x=1`.
+fn path_of(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.find('/').map_or("/", |i| &rest[i..])
+}
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Answers `method` requests whose path starts with `path` with `response`. Later registrations win.
+    pub fn respond(&self, method: impl Into<String>, path: impl Into<String>, response: HttpResponse) {
+        self.state().routes.push((method.into(), path.into(), response));
+    }
+
+    pub fn respond_json(&self, method: impl Into<String>, path: impl Into<String>, json: Value) {
+        self.respond(method, path, HttpResponse::json(200, &json));
+    }
+
+    /// Answers every websocket command with the frames `handler` returns for it.
+    pub fn on_ws_command(&self, handler: impl Fn(&Value) -> Vec<Value> + Send + Sync + 'static) {
+        self.state().ws_handler = Some(Arc::new(handler));
+    }
+
+    /// Every HTTP request made so far.
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.state().requests.clone()
+    }
+
+    /// Every websocket command sent so far.
+    pub fn ws_commands(&self) -> Vec<Value> {
+        self.state().ws_commands.clone()
+    }
+}
+
+#[async_trait]
+impl Transport for MemoryTransport {
+    async fn request(&self, request: HttpRequest) -> Result<HttpResponse, RequesterError> {
+        let mut state = self.state();
+        let path = path_of(&request.url);
+        let response = state.routes.iter().rev()
+            .find(|(method, prefix, _)| *method == request.method && path.starts_with(prefix.as_str()))
+            .map_or_else(|| HttpResponse::new(404, format!("no response registered for {} {}", request.method, path)), |(_, _, response)| response.clone());
+        state.requests.push(request);
+
+        Ok(response)
+    }
+
+    async fn connect_ws(&self, _url: &str, _headers: &HashMap<String, String>) -> Result<(WsSink, WsSource), RequesterError> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let sink = sink::unfold((self.clone(), tx), |(transport, tx), frame: WsFrame| async move {
+            let replies = match frame {
+                WsFrame::Text(text) => {
+                    let command = serde_json::from_str::<Value>(&text).map_err(|err| RequesterError::WsError(err.to_string()))?;
+                    let handler = {
+                        let mut state = transport.state();
+                        state.ws_commands.push(command.clone());
+                        state.ws_handler.clone()
+                    };
+                    handler.map_or_else(Vec::new, |handler| handler(&command)).into_iter().map(|reply| WsFrame::Text(reply.to_string())).collect()
+                }
+                WsFrame::Ping(data) => vec![WsFrame::Pong(data)],
+                WsFrame::Close => vec![WsFrame::Close],
+                _ => vec![],
+            };
+            for reply in replies {
+                let _ = tx.send(reply);
+            }
+
+            Ok::<_, RequesterError>((transport, tx))
+        });
+        let source = stream! {
+            while let Some(frame) = rx.recv().await {
+                yield Ok(frame);
+            }
+        };
+
+        Ok((Box::pin(sink), Box::pin(source)))
+    }
+}
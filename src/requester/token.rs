@@ -0,0 +1,88 @@
+use std::fmt::{self, Debug, Display};
+use async_trait::async_trait;
+use zeroize::Zeroizing;
+
+use super::RequesterError;
+
+/// A Character.AI token. It is wiped from memory when dropped and prints as `[REDACTED]`, so it can't end up in logs;
+/// the only way to read it is to build the `authorization` header or websocket cookie from it.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretToken(Zeroizing<String>);
+
+impl SecretToken {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self(Zeroizing::new(token.into()))
+    }
+
+    /// The value of the `authorization` header.
+    pub(crate) fn authorization(&self) -> String {
+        format!("Token {}", self.0.as_str())
+    }
+
+    /// The value of the `Cookie` header opening the websocket.
+    pub(crate) fn cookie(&self) -> String {
+        format!("HTTP_AUTHORIZATION=\"Token {}\"", self.0.as_str())
+    }
+}
+
+impl From<String> for SecretToken {
+    fn from(token: String) -> Self {
+        Self::new(token)
+    }
+}
+
+impl From<&str> for SecretToken {
+    fn from(token: &str) -> Self {
+        Self::new(token)
+    }
+}
+
+impl Debug for SecretToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretToken([REDACTED])")
+    }
+}
+
+impl Display for SecretToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+/// Supplies the token a client authenticates with, and a new one when Character.AI rejects it.
+///
+/// When a client has a provider, every request asks it for the token. A request answered with 401, or a websocket
+/// handshake that is refused, calls [`TokenProvider::refresh`] and is retried once with the new token.
+#[async_trait]
+pub trait TokenProvider: Debug + Send + Sync {
+    async fn token(&self) -> Result<SecretToken, RequesterError>;
+
+    /// A replacement for `rejected`, which the server refused. Return
+    /// [`RequesterError::AuthenticationError`] if there is none.
+    async fn refresh(&self, rejected: &SecretToken) -> Result<SecretToken, RequesterError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn tokens_are_redacted() {
+        let token = SecretToken::new("hunter2");
+        assert_eq!(format!("{:?} {}", token, token), "SecretToken([REDACTED]) [REDACTED]");
+        assert_eq!(token.authorization(), "Token hunter2");
+        assert_eq!(token, SecretToken::from("hunter2"));
+
+        let request = crate::requester::HttpRequest {
+            method: "GET".to_string(),
+            url: "https://plus.character.ai/chat/user/".to_string(),
+            headers: HashMap::from([("authorization".to_string(), token.authorization()), ("Cookie".to_string(), token.cookie())]),
+            body: None,
+        };
+        let debug = format!("{:?}", request);
+        assert!(!debug.contains("hunter2"));
+        assert!(debug.contains("[REDACTED]"));
+    }
+}
@@ -0,0 +1,215 @@
+use std::{collections::HashMap, fmt::{self, Debug}, pin::Pin};
+use async_trait::async_trait;
+use futures_util::{future, Sink, SinkExt, Stream, StreamExt};
+use http::Uri;
+use reqwest::{Client, Method};
+use serde_json::Value;
+use tokio_tungstenite::{connect_async, tungstenite::{self, protocol::Message, ClientRequestBuilder}};
+
+use super::{RequesterConfig, RequesterError};
+
+/// Headers that carry the token.
+const SECRET_HEADERS: [&str; 2] = ["authorization", "cookie"];
+
+/// `headers` with the values of the ones carrying the token replaced by `[REDACTED]`.
+pub(crate) fn redact(headers: &HashMap<String, String>) -> HashMap<String, String> {
+    headers.iter()
+        .map(|(name, value)| {
+            let value = if SECRET_HEADERS.contains(&name.to_lowercase().as_str()) { "[REDACTED]".to_string() } else { value.clone() };
+            (name.clone(), value)
+        })
+        .collect()
+}
+
+/// An HTTP request as handed to a [`Transport`]. Its `Debug` output leaves out the token.
+#[derive(Clone, PartialEq)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: HashMap<String, String>,
+    pub body: Option<Vec<u8>>,
+}
+
+impl fmt::Debug for HttpRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpRequest")
+            .field("method", &self.method)
+            .field("url", &self.url)
+            .field("headers", &redact(&self.headers))
+            .field("body", &self.body)
+            .finish()
+    }
+}
+
+impl HttpRequest {
+    /// The body parsed as JSON, if it is JSON.
+    pub fn json(&self) -> Option<Value> {
+        self.body.as_ref().and_then(|body| serde_json::from_slice(body).ok())
+    }
+}
+
+/// The raw answer to an [`HttpRequest`]. Status codes aren't interpreted by the transport; that's up to the
+/// [`crate::requester::Requester`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    /// Header names are lowercase.
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self { status, headers: HashMap::new(), body: body.into() }
+    }
+
+    pub fn json(status: u16, json: &Value) -> Self {
+        Self::new(status, json.to_string()).with_header("content-type", "application/json")
+    }
+
+    pub fn with_header(mut self, name: impl AsRef<str>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.as_ref().to_lowercase(), value.into());
+        self
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(String::as_str)
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.body
+    }
+}
+
+/// A websocket frame as sent or received through a [`Transport`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum WsFrame {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Close,
+}
+
+pub type WsSink = Pin<Box<dyn Sink<WsFrame, Error = RequesterError> + Send>>;
+pub type WsSource = Pin<Box<dyn Stream<Item = Result<WsFrame, RequesterError>> + Send>>;
+
+/// Moves bytes for a [`crate::requester::Requester`].
+///
+/// [`ReqwestTransport`] talks to the network; [`crate::requester::MemoryTransport`] answers from a script so
+/// the client can be tested offline.
+#[async_trait]
+pub trait Transport: Debug + Send + Sync {
+    async fn request(&self, request: HttpRequest) -> Result<HttpResponse, RequesterError>;
+
+    /// Opens a websocket, returning its sending and receiving halves. Rejected credentials should be reported as
+    /// [`RequesterError::AuthenticationError`].
+    async fn connect_ws(&self, url: &str, headers: &HashMap<String, String>) -> Result<(WsSink, WsSource), RequesterError>;
+}
+
+/// The default [`Transport`], using `reqwest` for HTTP and `tokio-tungstenite` for websockets.
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl Default for ReqwestTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReqwestTransport {
+    pub fn new() -> Self {
+        let client = Client::builder()
+            .build()
+            .expect("Failed to build client");
+
+        Self::with_client(client)
+    }
+
+    /// A transport applying the connect timeout of `config`. The request timeout is enforced by the
+    /// [`crate::requester::Requester`] itself.
+    pub fn from_config(config: &RequesterConfig) -> Self {
+        let mut builder = Client::builder();
+        if let Some(timeout) = config.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        Self::with_client(builder.build().expect("Failed to build client"))
+    }
+
+    pub fn with_client(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+impl From<WsFrame> for Message {
+    fn from(frame: WsFrame) -> Self {
+        match frame {
+            WsFrame::Text(text) => Message::Text(text.into()),
+            WsFrame::Binary(data) => Message::Binary(data.into()),
+            WsFrame::Ping(data) => Message::Ping(data.into()),
+            WsFrame::Pong(data) => Message::Pong(data.into()),
+            WsFrame::Close => Message::Close(None),
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for ReqwestTransport {
+    async fn request(&self, request: HttpRequest) -> Result<HttpResponse, RequesterError> {
+        let method = Method::from_bytes(request.method.as_bytes()).map_err(|_| RequesterError::RequestFailed(format!("invalid method {}", request.method)))?;
+        let mut req = self.client.request(method, request.url);
+
+        for (key, value) in request.headers.iter() {
+            req = req.header(key, value);
+        }
+
+        if let Some(body) = request.body {
+            req = req.body(body);
+        }
+
+        let res = req.send().await?;
+        let status = res.status().as_u16();
+        let headers = res.headers().iter()
+            .filter_map(|(name, value)| value.to_str().ok().map(|v| (name.as_str().to_string(), v.to_string())))
+            .collect();
+        let body = res.bytes().await?.to_vec();
+
+        Ok(HttpResponse { status, headers, body })
+    }
+
+    async fn connect_ws(&self, url: &str, headers: &HashMap<String, String>) -> Result<(WsSink, WsSource), RequesterError> {
+        let uri: Uri = url.parse().map_err(|_| RequesterError::WsError(format!("invalid websocket url {}", url)))?;
+        let builder = headers.iter().fold(ClientRequestBuilder::new(uri), |builder, (key, value)| builder.with_header(key, value));
+        let (ws_stream, _) = connect_async(builder)
+            .await
+            .map_err(|err| match err {
+                tungstenite::Error::Http(resp) if resp.status() == 401 || resp.status() == 403 => RequesterError::AuthenticationError,
+                err => RequesterError::WsError(format!("could not connect to websocket url {}: {}", url, err)),
+            })?;
+
+        let (sink, source) = ws_stream.split();
+        let sink = sink.sink_map_err(RequesterError::from).with(|frame: WsFrame| future::ok(Message::from(frame)));
+        let source = source.filter_map(|message| future::ready(match message {
+            Ok(Message::Text(text)) => Some(Ok(WsFrame::Text(text.to_string()))),
+            Ok(Message::Binary(data)) => Some(Ok(WsFrame::Binary(data.to_vec()))),
+            Ok(Message::Ping(data)) => Some(Ok(WsFrame::Ping(data.to_vec()))),
+            Ok(Message::Pong(data)) => Some(Ok(WsFrame::Pong(data.to_vec()))),
+            Ok(Message::Close(_)) => Some(Ok(WsFrame::Close)),
+            Ok(Message::Frame(_)) => None,
+            Err(err) => Some(Err(err.into())),
+        }));
+
+        Ok((Box::pin(sink), Box::pin(source)))
+    }
+}
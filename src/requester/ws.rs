@@ -0,0 +1,365 @@
+use std::{sync::{Arc, Mutex, MutexGuard, PoisonError}, time::{Duration, Instant}};
+use async_stream::stream;
+use futures_util::{SinkExt, Stream, StreamExt};
+use serde_json::Value;
+use tokio::{sync::{mpsc, oneshot}, task::JoinHandle, time};
+
+use super::{transport::{WsFrame, WsSink, WsSource}, RequesterError};
+
+type Frame = Result<Value, RequesterError>;
+type Outgoing = (WsFrame, oneshot::Sender<Result<(), RequesterError>>);
+
+/// Where the neo API puts the chat id of a command or of the frames it answers with.
+const CHAT_ID_POINTERS: [&str; 4] = ["/turn/turn_key/chat_id", "/chat/chat_id", "/turn_key/chat_id", "/chat_id"];
+
+/// Which incoming frames belong to a command.
+///
+/// Frames are matched on `request_id` first. Some neo responses (`update_primary_candidate`'s `ok`, for one) don't
+/// echo the request id, so those fall back to the chat the command was about. A route with neither receives every
+/// frame nobody else claimed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Route {
+    pub request_id: Option<String>,
+    pub chat_id: Option<String>,
+}
+
+impl Route {
+    pub fn of(message: &Value) -> Self {
+        let chat_id = [message, &message["payload"]].into_iter()
+            .flat_map(|v| CHAT_ID_POINTERS.iter().filter_map(move |p| v.pointer(p)))
+            .find_map(|v| v.as_str())
+            .map(String::from);
+
+        Self {
+            request_id: message.get("request_id").and_then(|v| v.as_str()).map(String::from),
+            chat_id,
+        }
+    }
+
+    fn is_catch_all(&self) -> bool {
+        self.request_id.is_none() && self.chat_id.is_none()
+    }
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    id: u64,
+    route: Route,
+    tx: mpsc::UnboundedSender<Frame>,
+}
+
+/// A snapshot of the websocket connection, see [`crate::requester::Requester::ws_health`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WsHealth {
+    pub connected: bool,
+    pub connected_since: Option<Instant>,
+    /// When the last frame of any kind (including pongs) was received.
+    pub last_frame: Option<Instant>,
+    /// The round trip time of the last answered ping.
+    pub latency: Option<Duration>,
+    /// How many commands are currently waiting on a response.
+    pub in_flight: usize,
+    /// How many times the connection has been replaced after dying.
+    pub reconnects: u32,
+    /// Why the connection last died.
+    pub last_error: Option<RequesterError>,
+}
+
+#[derive(Debug, Default)]
+struct Routes {
+    next_id: u64,
+    subscribers: Vec<Subscriber>,
+    health: WsHealth,
+}
+
+impl Routes {
+    fn target(&self, route: &Route) -> Option<usize> {
+        let by_request = route.request_id.as_ref().and_then(|id| self.subscribers.iter().position(|s| s.route.request_id.as_ref() == Some(id)));
+        let by_chat = || route.chat_id.as_ref().and_then(|id| self.subscribers.iter().position(|s| s.route.chat_id.as_ref() == Some(id)));
+
+        by_request.or_else(by_chat).or_else(|| self.subscribers.iter().position(|s| s.route.is_catch_all()))
+    }
+
+    fn dispatch(&mut self, frame: Value) {
+        let Some(i) = self.target(&Route::of(&frame)) else { return };
+        if self.subscribers[i].tx.send(Ok(frame)).is_err() {
+            self.subscribers.remove(i);
+        }
+    }
+
+    fn close(&mut self, err: RequesterError) {
+        if !self.health.connected {
+            return;
+        }
+        self.health.connected = false;
+        self.health.last_error = Some(err.clone());
+        for subscriber in self.subscribers.drain(..) {
+            let _ = subscriber.tx.send(Err(err.clone()));
+        }
+    }
+}
+
+fn lock(routes: &Mutex<Routes>) -> MutexGuard<'_, Routes> {
+    routes.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A neo websocket shared by every chat command.
+///
+/// A background task reads frames and routes each to the command it answers, while writes go through a second
+/// task, so any number of commands can be streaming over the socket at once. The reader also pings the server
+/// every `ping_interval`; if nothing at all comes back within `pong_timeout` the connection is treated as
+/// half-open and every waiting command fails with [`RequesterError::WsConnectionLost`].
+#[derive(Debug)]
+pub(crate) struct WsConnection {
+    writer: mpsc::UnboundedSender<Outgoing>,
+    routes: Arc<Mutex<Routes>>,
+    reader: JoinHandle<()>,
+}
+
+impl WsConnection {
+    pub fn spawn((mut sink, mut source): (WsSink, WsSource), ping_interval: Duration, pong_timeout: Duration) -> Self {
+        let (writer, mut outgoing) = mpsc::unbounded_channel::<Outgoing>();
+        let routes = Arc::new(Mutex::new(Routes::default()));
+        {
+            let mut routes = lock(&routes);
+            routes.health.connected = true;
+            routes.health.connected_since = Some(Instant::now());
+        }
+
+        tokio::spawn(async move {
+            while let Some((message, ack)) = outgoing.recv().await {
+                let _ = ack.send(sink.send(message).await);
+            }
+            let _ = sink.close().await;
+        });
+
+        let reader_routes = Arc::clone(&routes);
+        let pinger = writer.clone();
+        let reader = tokio::spawn(async move {
+            let mut ping = time::interval_at(time::Instant::now() + ping_interval, ping_interval);
+            let mut ping_sent: Option<Instant> = None;
+
+            let err = loop {
+                let deadline = time::Instant::from_std(ping_sent.unwrap_or_else(Instant::now) + pong_timeout);
+
+                tokio::select! {
+                    message = source.next() => {
+                        let now = Instant::now();
+                        let mut routes = lock(&reader_routes);
+                        routes.health.last_frame = Some(now);
+                        if let Some(sent) = ping_sent.take() && matches!(message, Some(Ok(WsFrame::Pong(_)))) {
+                            routes.health.latency = Some(now - sent);
+                        }
+
+                        match message {
+                            Some(Ok(WsFrame::Text(text))) => {
+                                // Frames that aren't JSON can't be routed to anyone, so they're dropped.
+                                if let Ok(frame) = serde_json::from_str::<Value>(&text) {
+                                    routes.dispatch(frame);
+                                }
+                            }
+                            Some(Ok(WsFrame::Close)) => break RequesterError::WsClosed,
+                            None => break RequesterError::WsConnectionLost("the server went away without closing the connection".to_string()),
+                            Some(Ok(_)) => continue,
+                            Some(Err(err)) => break RequesterError::WsConnectionLost(err.to_string()),
+                        }
+                    }
+                    _ = ping.tick() => {
+                        if ping_sent.is_none() {
+                            let (ack, _) = oneshot::channel();
+                            if pinger.send((WsFrame::Ping(Vec::new()), ack)).is_err() {
+                                break RequesterError::WsClosed;
+                            }
+                            ping_sent = Some(Instant::now());
+                        }
+                    }
+                    _ = time::sleep_until(deadline), if ping_sent.is_some() => {
+                        break RequesterError::WsConnectionLost(format!("no response to a ping within {:?}", pong_timeout));
+                    }
+                }
+            };
+            lock(&reader_routes).close(err);
+        });
+
+        Self { writer, routes, reader }
+    }
+
+    pub fn health(&self) -> WsHealth {
+        let routes = lock(&self.routes);
+        WsHealth { in_flight: routes.subscribers.iter().filter(|s| !s.route.is_catch_all()).count(), ..routes.health.clone() }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        !lock(&self.routes).health.connected
+    }
+
+    pub async fn send(&self, message: &Value) -> Result<(), RequesterError> {
+        let (ack, acked) = oneshot::channel();
+        self.writer.send((WsFrame::Text(message.to_string()), ack)).map_err(|_| RequesterError::WsClosed)?;
+        acked.await.map_err(|_| RequesterError::WsClosed)?
+    }
+
+    /// Starts collecting the frames matching `route`. Subscribe before sending the command so no frame is missed.
+    pub fn subscribe(&self, route: Route) -> Result<Subscription, RequesterError> {
+        let mut routes = lock(&self.routes);
+        if !routes.health.connected {
+            return Err(RequesterError::WsClosed);
+        }
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let id = routes.next_id;
+        routes.next_id += 1;
+        routes.subscribers.push(Subscriber { id, route, tx });
+
+        Ok(Subscription { id, routes: Arc::clone(&self.routes), rx })
+    }
+
+    pub async fn close(self) {
+        let (ack, acked) = oneshot::channel();
+        if self.writer.send((WsFrame::Close, ack)).is_ok() {
+            let _ = acked.await;
+        }
+    }
+}
+
+impl Drop for WsConnection {
+    fn drop(&mut self) {
+        self.reader.abort();
+        lock(&self.routes).close(RequesterError::WsClosed);
+    }
+}
+
+/// The frames routed to one command. Dropping it stops the routing.
+pub(crate) struct Subscription {
+    id: u64,
+    routes: Arc<Mutex<Routes>>,
+    rx: mpsc::UnboundedReceiver<Frame>,
+}
+
+impl Subscription {
+    /// The routed frames, ending after the first error (which is always the connection going away).
+    pub fn into_stream(mut self) -> impl Stream<Item = Frame> {
+        stream! {
+            while let Some(frame) = self.rx.recv().await {
+                let failed = frame.is_err();
+                yield frame;
+                if failed {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        lock(&self.routes).subscribers.retain(|s| s.id != self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tokio_tungstenite::tungstenite::Message;
+
+    use super::*;
+
+    #[test]
+    fn routes_by_request_id_then_chat_id() {
+        let mut routes = Routes::default();
+        routes.health.connected = true;
+        let (a_tx, mut a_rx) = mpsc::unbounded_channel();
+        let (b_tx, mut b_rx) = mpsc::unbounded_channel();
+        let (rest_tx, mut rest_rx) = mpsc::unbounded_channel();
+        routes.subscribers.push(Subscriber { id: 0, route: Route::of(&json!({ "request_id": "a", "payload": { "turn_key": { "chat_id": "x" } } })), tx: a_tx });
+        routes.subscribers.push(Subscriber { id: 1, route: Route::of(&json!({ "request_id": "b", "payload": { "chat_id": "y" } })), tx: b_tx });
+        routes.subscribers.push(Subscriber { id: 2, route: Route::default(), tx: rest_tx });
+
+        routes.dispatch(json!({ "command": "update_turn", "request_id": "b", "turn": { "turn_key": { "chat_id": "x" } } }));
+        routes.dispatch(json!({ "command": "ok", "chat_id": "x" }));
+        routes.dispatch(json!({ "command": "update_turn", "turn": { "turn_key": { "chat_id": "y" } } }));
+        routes.dispatch(json!({ "command": "something_else" }));
+
+        assert_eq!(a_rx.try_recv().unwrap().unwrap()["command"], "ok");
+        assert_eq!(b_rx.try_recv().unwrap().unwrap()["request_id"], "b");
+        assert_eq!(b_rx.try_recv().unwrap().unwrap()["turn"]["turn_key"]["chat_id"], "y");
+        assert_eq!(rest_rx.try_recv().unwrap().unwrap()["command"], "something_else");
+        assert!(a_rx.try_recv().is_err() && b_rx.try_recv().is_err() && rest_rx.try_recv().is_err());
+
+        routes.close(RequesterError::WsClosed);
+        assert_eq!(a_rx.try_recv().unwrap(), Err(RequesterError::WsClosed));
+    }
+
+    #[tokio::test]
+    async fn concurrent_commands_get_their_own_frames() {
+        use crate::requester::{Requester, RequesterConfig};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/", listener.local_addr().unwrap());
+
+        // Waits for both commands, then answers them in reverse order.
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            let mut ids = vec![];
+            while ids.len() < 2 {
+                if let Some(Ok(Message::Text(text))) = ws.next().await {
+                    ids.push(serde_json::from_str::<Value>(&text).unwrap()["request_id"].as_str().unwrap().to_string());
+                }
+            }
+            for id in ids.iter().rev() {
+                ws.send(Message::Text(json!({ "command": "done", "request_id": id }).to_string().into())).await.unwrap();
+            }
+        });
+
+        let requester = Requester::with_config(RequesterConfig::new().with_ws_url(url));
+        let a = requester.ws_send_and_receive(&json!({ "command": "a", "request_id": "a" }), "token".into()).await.unwrap();
+        let b = requester.ws_send_and_receive(&json!({ "command": "b", "request_id": "b" }), "token".into()).await.unwrap();
+        let (mut a, mut b) = (Box::pin(a), Box::pin(b));
+        let (a, b) = tokio::join!(a.next(), b.next());
+
+        assert_eq!(a.unwrap().unwrap()["request_id"], "a");
+        assert_eq!(b.unwrap().unwrap()["request_id"], "b");
+    }
+
+    #[tokio::test]
+    async fn half_open_connections_fail_in_flight_commands_and_reconnect() {
+        use crate::requester::{Requester, RequesterConfig};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            // The first connection never reads, so pings go unanswered.
+            let (socket, _) = listener.accept().await.unwrap();
+            let _silent = tokio_tungstenite::accept_async(socket).await.unwrap();
+
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            while let Some(Ok(message)) = ws.next().await {
+                if let Message::Text(text) = message {
+                    let request_id = serde_json::from_str::<Value>(&text).unwrap()["request_id"].clone();
+                    ws.send(Message::Text(json!({ "command": "done", "request_id": request_id }).to_string().into())).await.unwrap();
+                }
+            }
+        });
+
+        let config = RequesterConfig::new()
+            .with_ws_url(url)
+            .with_ws_heartbeat(Duration::from_millis(50), Duration::from_millis(50));
+        let requester = Requester::with_config(config);
+
+        let stream = requester.ws_send_and_receive(&json!({ "command": "a" }), "token".into()).await.unwrap();
+        let err = Box::pin(stream).next().await.unwrap().unwrap_err();
+        assert!(matches!(err, RequesterError::WsConnectionLost(_)), "{:?}", err);
+        assert!(!requester.ws_health().await.connected);
+
+        let stream = requester.ws_send_and_receive(&json!({ "command": "b" }), "token".into()).await.unwrap();
+        assert_eq!(Box::pin(stream).next().await.unwrap().unwrap()["command"], "done");
+
+        let health = requester.ws_health().await;
+        assert!(health.connected);
+        assert_eq!(health.reconnects, 1);
+        assert!(matches!(health.last_error, Some(RequesterError::WsConnectionLost(_))));
+    }
+}
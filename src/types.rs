@@ -1,5 +1,9 @@
 pub mod character;
 pub mod chat;
 pub mod enums;
+pub mod ids;
 pub mod media;
-pub mod user;
\ No newline at end of file
+pub mod parse;
+pub mod timestamp;
+pub mod user;
+pub mod ws;
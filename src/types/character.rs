@@ -1,10 +1,10 @@
 use serde_json::{json, Map, Value};
 
-use crate::types::{enums::*, media::*};
+use crate::types::{enums::*, ids::*, media::*, parse::{impl_from_json, impl_serde, JsonReader}};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Character {
-    pub id: String,
+    pub id: CharacterId,
     pub name: String,
     pub description: String,
     pub definition: String,
@@ -16,8 +16,8 @@ pub struct Character {
     pub author_username: Option<String>,
     pub num_interactions: Option<i64>,
     pub internal_id: String,
-    pub voice_id: String,
-    pub default_voice_id: String,
+    pub voice_id: VoiceId,
+    pub default_voice_id: VoiceId,
     pub identifier: String,
     pub copyable: bool,
     pub starter_prompts: Map<String, Value>,
@@ -30,7 +30,8 @@ pub struct Character {
 }
 
 impl Character {
-    pub fn new(id: impl Into<String>, name: impl Into<String>, description: impl Into<String>, definition: impl Into<String>, greeting: impl Into<String>, avatar: Option<Avatar>, visibility: Visibility, upvotes: Option<i64>, title: impl Into<String>, author_username: Option<String>, num_interactions: Option<i64>, internal_id: impl Into<String>, voice_id: impl Into<String>, default_voice_id: impl Into<String>, identifier: impl Into<String>, copyable: bool, starter_prompts: Map<String, Value>, comments_enabled: bool, songs: Vec<String>, image_gen_enabled: bool, base_image_prompt: impl Into<String>, image_prompt_regex: impl Into<String>, strip_image_prompt_from_message: bool) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(id: impl Into<CharacterId>, name: impl Into<String>, description: impl Into<String>, definition: impl Into<String>, greeting: impl Into<String>, avatar: Option<Avatar>, visibility: Visibility, upvotes: Option<i64>, title: impl Into<String>, author_username: Option<String>, num_interactions: Option<i64>, internal_id: impl Into<String>, voice_id: impl Into<VoiceId>, default_voice_id: impl Into<VoiceId>, identifier: impl Into<String>, copyable: bool, starter_prompts: Map<String, Value>, comments_enabled: bool, songs: Vec<String>, image_gen_enabled: bool, base_image_prompt: impl Into<String>, image_prompt_regex: impl Into<String>, strip_image_prompt_from_message: bool) -> Self {
         Self {
             id: id.into(),
             name: name.into(),
@@ -58,55 +59,55 @@ impl Character {
         }
     }
 
-    pub fn from_json(json: &Value) -> Self {
-        let blank = json!("");
-        let blank_bool = json!(false);
+    pub(crate) fn read_json(r: &mut JsonReader, json: &Value) -> Self {
+        let name = if json.get("participant__name").is_some() { r.str(json, "participant__name") } else { r.str(json, "name") };
 
         Self::new(
-            json.get("external_id").unwrap_or(&blank).as_str().unwrap(),
-            json.get("participant__name").unwrap_or(json.get("name").unwrap_or(&blank)).as_str().unwrap(),
-            json.get("description").unwrap_or(&blank).as_str().unwrap(),
-            json.get("definition").unwrap_or(&blank).as_str().unwrap_or(""),
-            json.get("greeting").unwrap_or(&blank).as_str().unwrap(),
-            Avatar::from_json(&json),
-            Visibility::from_string(json.get("visibility").unwrap_or(&json!("PUBLIC")).as_str().unwrap()),
-            json.get("upvotes").and_then(|v| v.as_i64().map(i64::from)),
-            json.get("title").unwrap_or(&blank).as_str().unwrap(),
-            json.get("user__username").and_then(|v| v.as_str().map(String::from)),
-            json.get("participant__num_interactions").and_then(|v| v.as_i64().map(i64::from)),
-            json.get("participant__user__username").unwrap_or(&blank).as_str().unwrap(),
-            json.get("voice_id").unwrap_or(&blank).as_str().unwrap(),
-            json.get("default_voice_id").unwrap_or(&blank).as_str().unwrap(),
-            json.get("identifier").unwrap_or(&blank).as_str().unwrap(),
-            json.get("copyable").unwrap_or(&blank_bool).as_bool().unwrap(),
-            json.get("starter_prompts").unwrap_or(&json!({})).as_object().unwrap().clone(),
-            json.get("comments_enabled").unwrap_or(&blank_bool).as_bool().unwrap(),
-            json.get("songs").unwrap_or(&json!([])).as_array().unwrap().into_iter().filter_map(|v| v.as_str()).map(|v| v.to_string()).collect(),
-            json.get("img_gen_enabled").unwrap_or(&blank_bool).as_bool().unwrap(),
-            json.get("base_img_prompt").unwrap_or(&blank).as_str().unwrap(),
-            json.get("img_prompt_regex").unwrap_or(&blank).as_str().unwrap(),
-            json.get("strip_img_prompt_from_msg").unwrap_or(&blank_bool).as_bool().unwrap(),
+            r.str(json, "external_id"),
+            name,
+            r.str(json, "description"),
+            r.str(json, "definition"),
+            r.str(json, "greeting"),
+            Avatar::read_json(r, json),
+            Visibility::from_string(r.opt_str(json, "visibility").unwrap_or("PUBLIC".to_string())),
+            r.opt_i64(json, "upvotes"),
+            r.str(json, "title"),
+            r.opt_str(json, "user__username"),
+            r.opt_i64(json, "participant__num_interactions"),
+            r.str(json, "participant__user__username"),
+            r.str(json, "voice_id"),
+            r.str(json, "default_voice_id"),
+            r.str(json, "identifier"),
+            r.bool(json, "copyable", false),
+            r.object(json, "starter_prompts"),
+            r.bool(json, "comments_enabled", false),
+            r.str_array(json, "songs"),
+            r.bool(json, "img_gen_enabled", false),
+            r.str(json, "base_img_prompt"),
+            r.str(json, "img_prompt_regex"),
+            r.bool(json, "strip_img_prompt_from_msg", false),
         )
     }
 
     pub fn to_json(&self) -> Value {
-        let avi_file = if self.avatar.is_none() { Value::Null } else { json!(&self.avatar.as_ref().unwrap().file_name) };
+        let avi_file = self.avatar.as_ref().map_or(Value::Null, |a| json!(&a.file_name));
 
         json!({
-            "external_id": self.id,
+            "external_id": self.id.as_str(),
             "participant__name": self.name,
             "description": self.description,
             "definition": self.definition,
+            "greeting": self.greeting,
             "avatar_file_name": avi_file,
             "avatar_rel_path": avi_file,
             "visibility": self.visibility.to_string(),
-            "upvotes": self.upvotes.unwrap_or(0),
+            "upvotes": self.upvotes,
             "title": self.title,
-            "user__username": self.author_username.as_ref().unwrap_or(&"".to_string()),
-            "participant__num_interactions": self.num_interactions.unwrap_or(0),
+            "user__username": self.author_username,
+            "participant__num_interactions": self.num_interactions,
             "participant__user__username": self.internal_id,
-            "voice_id": self.voice_id,
-            "default_voice_id": self.default_voice_id,
+            "voice_id": self.voice_id.as_str(),
+            "default_voice_id": self.default_voice_id.as_str(),
             "identifier": self.identifier,
             "copyable": self.copyable,
             "starter_prompts": self.starter_prompts,
@@ -120,9 +121,9 @@ impl Character {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PartialCharacter {
-    pub id: String,
+    pub id: CharacterId,
     pub name: String,
     pub description: String,
     pub definition: String,
@@ -136,7 +137,8 @@ pub struct PartialCharacter {
 }
 
 impl PartialCharacter {
-    pub fn new(id: impl Into<String>, name: impl Into<String>, description: impl Into<String>, definition: impl Into<String>, greeting: impl Into<String>, avatar: Option<Avatar>, visibility: Visibility, upvotes: Option<i64>, title: impl Into<String>, author_username: Option<String>, num_interactions: Option<i64>) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(id: impl Into<CharacterId>, name: impl Into<String>, description: impl Into<String>, definition: impl Into<String>, greeting: impl Into<String>, avatar: Option<Avatar>, visibility: Visibility, upvotes: Option<i64>, title: impl Into<String>, author_username: Option<String>, num_interactions: Option<i64>) -> Self {
         Self {
             id: id.into(),
             name: name.into(),
@@ -152,39 +154,43 @@ impl PartialCharacter {
         }
     }
 
-    pub fn from_json(json: &Value) -> Self {
-        let blank = json!("");
+    pub(crate) fn read_json(r: &mut JsonReader, json: &Value) -> Self {
+        let name = if json.get("participant__name").is_some() { r.str(json, "participant__name") } else { r.str(json, "name") };
 
         Self::new(
-            json.get("external_id").unwrap_or(&blank).as_str().unwrap(),
-            json.get("participant__name").unwrap_or(json.get("name").unwrap_or(&blank)).as_str().unwrap(),
-            json.get("description").unwrap_or(&blank).as_str().unwrap(),
-            json.get("definition").unwrap_or(&blank).as_str().unwrap_or(""),
-            json.get("greeting").unwrap_or(&blank).as_str().unwrap(),
-            Avatar::from_json(&json),
-            Visibility::from_string(json.get("visibility").unwrap_or(&json!("PUBLIC")).as_str().unwrap_or("PUBLIC")),
-            json.get("upvotes").and_then(|v| v.as_i64().map(i64::from)),
-            json.get("title").unwrap_or(&blank).as_str().unwrap(),
-            json.get("user__username").and_then(|v| v.as_str().map(String::from)),
-            json.get("participant__num_interactions").and_then(|v| v.as_i64().map(i64::from))
+            r.str(json, "external_id"),
+            name,
+            r.str(json, "description"),
+            r.str(json, "definition"),
+            r.str(json, "greeting"),
+            Avatar::read_json(r, json),
+            Visibility::from_string(r.opt_str(json, "visibility").unwrap_or("PUBLIC".to_string())),
+            r.opt_i64(json, "upvotes"),
+            r.str(json, "title"),
+            r.opt_str(json, "user__username"),
+            r.opt_i64(json, "participant__num_interactions")
         )
     }
 
     pub fn to_json(&self) -> Value {
-        let avi_file = if self.avatar.is_none() { Value::Null } else { json!(&self.avatar.as_ref().unwrap().file_name) };
+        let avi_file = self.avatar.as_ref().map_or(Value::Null, |a| json!(&a.file_name));
 
         json!({
-            "external_id": self.id,
+            "external_id": self.id.as_str(),
             "participant__name": self.name,
             "description": self.description,
             "definition": self.definition,
+            "greeting": self.greeting,
             "avatar_file_name": avi_file,
             "avatar_rel_path": avi_file,
             "visibility": self.visibility.to_string(),
-            "upvotes": self.upvotes.unwrap_or(0),
+            "upvotes": self.upvotes,
             "title": self.title,
-            "user__username": self.author_username.as_ref().unwrap_or(&"".to_string()),
-            "participant__num_interactions": self.num_interactions.unwrap_or(0)
+            "user__username": self.author_username,
+            "participant__num_interactions": self.num_interactions
         })
     }
-}
\ No newline at end of file
+}
+
+impl_from_json!(Character, PartialCharacter);
+impl_serde!(Character, PartialCharacter);
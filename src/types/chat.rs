@@ -1,96 +1,217 @@
 use std::collections::HashMap;
 use serde_json::{json, Value};
 
-#[derive(Debug)]
+use crate::types::{enums::*, ids::*, media::*, parse::{impl_from_json, impl_serde, JsonReader}, timestamp::Timestamp};
+
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct ChatHistory {
-    
+    pub chat_id: ChatId,
+    pub create_time: Option<Timestamp>,
+    pub last_interaction_time: Option<Timestamp>,
+    pub preview_messages: Vec<Value>
 }
 
 impl ChatHistory {
-    pub fn new() -> Self {
+    pub fn new(chat_id: impl Into<ChatId>, create_time: Option<Timestamp>, last_interaction_time: Option<Timestamp>, preview_messages: Vec<Value>) -> Self {
         Self {
-
+            chat_id: chat_id.into(),
+            create_time,
+            last_interaction_time,
+            preview_messages
         }
     }
 
-    pub fn from_json(json: &Value) -> Self {
-        Self::new()
+    pub(crate) fn read_json(r: &mut JsonReader, json: &Value) -> Self {
+        let preview_messages = match r.value(json, "msgs") {
+            Value::Array(msgs) => msgs,
+            _ => vec![]
+        };
+
+        Self::new(
+            r.str(json, "external_id"),
+            r.opt_timestamp(json, "created"),
+            r.opt_timestamp(json, "last_interaction"),
+            preview_messages
+        )
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "external_id": self.chat_id.as_str(),
+            "created": self.create_time.map(|t| t.to_string()),
+            "last_interaction": self.last_interaction_time.map(|t| t.to_string()),
+            "msgs": self.preview_messages
+        })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Chat {
-    
+    pub id: ChatId,
+    pub character_id: CharacterId,
+    pub character_name: String,
+    pub character_avatar: Option<Avatar>,
+    pub creator_id: UserId,
+    pub visibility: Visibility,
+    pub chat_type: String,
+    pub create_time: Option<Timestamp>,
+    pub name: Option<String>,
+    pub preferred_model_type: Option<String>,
+    pub preview_turns: Vec<Turn>
 }
 
 impl Chat {
-    pub fn new() -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(id: impl Into<ChatId>, character_id: impl Into<CharacterId>, character_name: impl Into<String>, character_avatar: Option<Avatar>, creator_id: impl Into<UserId>, visibility: Visibility, chat_type: impl Into<String>, create_time: Option<Timestamp>, name: Option<String>, preferred_model_type: Option<String>, preview_turns: Vec<Turn>) -> Self {
         Self {
-
+            id: id.into(),
+            character_id: character_id.into(),
+            character_name: character_name.into(),
+            character_avatar,
+            creator_id: creator_id.into(),
+            visibility,
+            chat_type: chat_type.into(),
+            create_time,
+            name,
+            preferred_model_type,
+            preview_turns
         }
     }
 
-    pub fn from_json(json: &Value) -> Self {
-        Self::new()
+    pub(crate) fn read_json(r: &mut JsonReader, json: &Value) -> Self {
+        // Neo prefixes the usual visibility names, e.g. `VISIBILITY_PRIVATE`.
+        let visibility = r.opt_str(json, "visibility").unwrap_or("VISIBILITY_PRIVATE".to_string());
+
+        Self::new(
+            r.str(json, "chat_id"),
+            r.str(json, "character_id"),
+            r.str(json, "character_name"),
+            r.opt_str(json, "character_avatar_uri").filter(|s| !s.is_empty()).map(Avatar::new),
+            r.opt_i64(json, "creator_id").map(|id| id.to_string()).unwrap_or_default(),
+            Visibility::from_string(visibility.trim_start_matches("VISIBILITY_")),
+            r.opt_str(json, "type").unwrap_or("TYPE_ONE_ON_ONE".to_string()),
+            r.opt_timestamp(json, "create_time"),
+            r.opt_str(json, "name"),
+            r.opt_str(json, "preferred_model_type"),
+            r.array(json, "preview_turns", Turn::read_json)
+        )
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "chat_id": self.id.as_str(),
+            "character_id": self.character_id.as_str(),
+            "character_name": self.character_name,
+            "character_avatar_uri": self.character_avatar.as_ref().map(|a| &a.file_name),
+            "creator_id": self.creator_id.as_str(),
+            "visibility": format!("VISIBILITY_{}", self.visibility.to_string()),
+            "type": self.chat_type,
+            "create_time": self.create_time.map(|t| t.to_string()),
+            "name": self.name,
+            "preferred_model_type": self.preferred_model_type,
+            "preview_turns": self.preview_turns.iter().map(|t| t.to_json()).collect::<Vec<_>>()
+        })
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Candidate {
-    pub id: String,
+    pub id: CandidateId,
     pub text: String,
     pub is_final: bool,
     pub safety_truncated: bool,
-    pub create_time: Option<String>
+    pub create_time: Option<Timestamp>,
+    /// The candidate this one was edited from.
+    pub base_candidate_id: Option<CandidateId>,
+    /// Who last edited the candidate, if anyone did.
+    pub editor_id: Option<i64>,
+    pub editor_name: Option<String>,
+    /// The path of an image generated for the candidate, relative to the media host.
+    pub image_rel_path: Option<String>
 }
 
 impl Candidate {
-    pub fn new(id: impl Into<String>, text: impl Into<String>, is_final: bool, safety_truncated: bool, create_time: Option<String>) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(id: impl Into<CandidateId>, text: impl Into<String>, is_final: bool, safety_truncated: bool, create_time: Option<Timestamp>, base_candidate_id: Option<CandidateId>, editor_id: Option<i64>, editor_name: Option<String>, image_rel_path: Option<String>) -> Self {
         Self {
             id: id.into(),
             text: text.into(),
             is_final,
             safety_truncated,
-            create_time
+            create_time,
+            base_candidate_id,
+            editor_id,
+            editor_name,
+            image_rel_path
         }
     }
 
-    pub fn from_json(json: &Value) -> Self {
-        let blank = json!("");
-        let blank_bool = json!(false);
+    pub(crate) fn read_json(r: &mut JsonReader, json: &Value) -> Self {
+        let editor = json.get("editor").unwrap_or(&Value::Null);
+        let (editor_id, editor_name) = r.nested(editor, "editor", |r, e| (r.opt_i64(e, "author_id"), r.opt_str(e, "name")));
 
         Self::new(
-            json.get("candidate_id").unwrap_or(&blank).as_str().unwrap(),
-            json.get("raw_content").unwrap_or(&blank).as_str().unwrap(),
-            json.get("is_final").unwrap_or(&blank_bool).as_bool().unwrap_or(false),
-            json.get("safety_truncated").unwrap_or(&blank_bool).as_bool().unwrap_or(false),
-            json.get("create_time").and_then(|v| v.as_str().map(String::from))
+            r.str(json, "candidate_id"),
+            r.str(json, "raw_content"),
+            r.bool(json, "is_final", false),
+            r.bool(json, "safety_truncated", false),
+            r.opt_timestamp(json, "create_time"),
+            r.opt_str(json, "base_candidate_id").filter(|id| !id.is_empty()).map(CandidateId::from),
+            editor_id,
+            editor_name,
+            r.opt_str(json, "tti_image_rel_path").filter(|path| !path.is_empty())
         )
     }
+
+    pub fn to_json(&self) -> Value {
+        let editor = match (self.editor_id, &self.editor_name) {
+            (None, None) => Value::Null,
+            (id, name) => json!({ "author_id": id.map(|id| id.to_string()), "name": name })
+        };
+
+        json!({
+            "candidate_id": self.id.as_str(),
+            "raw_content": self.text,
+            "is_final": self.is_final,
+            "safety_truncated": self.safety_truncated,
+            "create_time": self.create_time.map(|t| t.to_string()),
+            "base_candidate_id": self.base_candidate_id.as_ref().map(CandidateId::as_str),
+            "editor": editor,
+            "tti_image_rel_path": self.image_rel_path
+        })
+    }
+
+    /// Whether a user changed the text the character wrote.
+    pub fn is_edited(&self) -> bool {
+        self.editor_id.is_some() || self.base_candidate_id.is_some()
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Turn {
-    pub id: String,
-    pub chat_id: String,
-    pub create_time: Option<String>,
-    pub last_update_time: Option<String>,
-    pub state: Value,
+    pub id: TurnId,
+    pub chat_id: ChatId,
+    pub create_time: Option<Timestamp>,
+    pub last_update_time: Option<Timestamp>,
+    pub state: TurnState,
+    pub is_pinned: bool,
     pub author_id: i64,
     pub author_name: String,
     pub author_is_human: bool,
-    pub primary_candidate_id: Option<String>,
-    pub candidates: HashMap<String, Candidate>
+    pub primary_candidate_id: Option<CandidateId>,
+    pub candidates: HashMap<CandidateId, Candidate>
 }
 
 impl Turn {
-    pub fn new(id: impl Into<String>, chat_id: impl Into<String>, create_time: Option<String>, last_update_time: Option<String>, state: Value, author_id: i64, author_name: impl Into<String>, author_is_human: bool, primary_candidate_id: Option<String>, candidates: HashMap<String, Candidate>) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(id: impl Into<TurnId>, chat_id: impl Into<ChatId>, create_time: Option<Timestamp>, last_update_time: Option<Timestamp>, state: TurnState, is_pinned: bool, author_id: i64, author_name: impl Into<String>, author_is_human: bool, primary_candidate_id: Option<CandidateId>, candidates: HashMap<CandidateId, Candidate>) -> Self {
         Self {
             id: id.into(),
             chat_id: chat_id.into(),
             create_time,
             last_update_time,
             state,
+            is_pinned,
             author_id,
             author_name: author_name.into(),
             author_is_human,
@@ -99,26 +220,47 @@ impl Turn {
         }
     }
 
-    pub fn from_json(json: &Value) -> Self {
-        let t = json.get("turn_key").expect("turn_key key should be present within JSON Value provided");
-        let author = t.get("author").expect("author key should be present within turn_key object in the JSON Value provided");
-
-        let blank = json!("");
+    pub(crate) fn read_json(r: &mut JsonReader, json: &Value) -> Self {
+        let t = r.required(json, "turn_key");
+        let (id, chat_id) = r.nested(t, "turn_key", |r, t| (r.str(t, "turn_id"), r.str(t, "chat_id")));
+        let author = r.required(json, "author");
+        let (author_id, author_name, author_is_human) = r.nested(author, "author", |r, a| (r.i64(a, "author_id", 0), r.str(a, "name"), r.bool(a, "is_human", false)));
 
         Self::new(
-            t.get("candidate_id").unwrap_or(&blank).as_str().unwrap(),
-            t.get("chat_id").unwrap_or(&blank).as_str().unwrap(),
-            json.get("create_time").and_then(|v| v.as_str().map(String::from)),
-            json.get("create_time").and_then(|v| v.as_str().map(String::from)),
-            json.get("state").unwrap_or(&blank).clone(),
-            author.get("author_id").unwrap_or(&json!(0)).as_i64().unwrap_or(0),
-            author.get("name").unwrap_or(&blank).as_str().unwrap_or(""),
-            author.get("is_human").unwrap_or(&json!(false)).as_bool().unwrap_or(false),
-            json.get("primary_candidate_id").and_then(|v| v.as_str().map(String::from)),
-            json.get("candidates").unwrap_or(&json!([])).as_array().unwrap_or(&vec![]).into_iter().map(|v| Candidate::from_json(&v)).map(|v| (v.id.clone(), v)).collect()
+            id,
+            chat_id,
+            r.opt_timestamp(json, "create_time"),
+            r.opt_timestamp(json, "last_update_time"),
+            r.opt_str(json, "state").map_or(TurnState::Ok, TurnState::from_string),
+            r.bool(json, "is_pinned", false),
+            author_id,
+            author_name,
+            author_is_human,
+            r.opt_str(json, "primary_candidate_id").map(CandidateId::from),
+            r.array(json, "candidates", Candidate::read_json).into_iter().map(|v| (v.id.clone(), v)).collect()
         )
     }
 
+    pub fn to_json(&self) -> Value {
+        json!({
+            "turn_key": {
+                "chat_id": self.chat_id.as_str(),
+                "turn_id": self.id.as_str()
+            },
+            "create_time": self.create_time.map(|t| t.to_string()),
+            "last_update_time": self.last_update_time.map(|t| t.to_string()),
+            "state": self.state.to_string(),
+            "is_pinned": self.is_pinned,
+            "author": {
+                "author_id": self.author_id.to_string(),
+                "name": self.author_name,
+                "is_human": self.author_is_human
+            },
+            "primary_candidate_id": self.primary_candidate_id.as_ref().map(CandidateId::as_str),
+            "candidates": self.candidates.values().map(|c| c.to_json()).collect::<Vec<_>>()
+        })
+    }
+
     pub fn get_candidates(&self) -> Vec<Candidate> {
         self.candidates.values().cloned().collect()
     } 
@@ -130,4 +272,137 @@ impl Turn {
             None
         }
     }
-}
\ No newline at end of file
+}
+
+/// Ordering helpers for lists of turns, which the API returns newest first. Turns without a `create_time` count
+/// as older than every turn with one.
+pub trait TurnSliceExt {
+    /// Sorts oldest first, keeping the order of turns created at the same time.
+    fn sort_chronologically(&mut self);
+
+    /// Sorts by when each turn last changed, oldest first, falling back to when it was created.
+    fn sort_by_last_update(&mut self);
+
+    fn oldest(&self) -> Option<&Turn>;
+
+    fn newest(&self) -> Option<&Turn>;
+
+    /// The turns created at or after `start` and before `end`, in their current order.
+    fn created_between(&self, start: Timestamp, end: Timestamp) -> Vec<&Turn>;
+}
+
+impl TurnSliceExt for [Turn] {
+    fn sort_chronologically(&mut self) {
+        self.sort_by_key(|turn| turn.create_time);
+    }
+
+    fn sort_by_last_update(&mut self) {
+        self.sort_by_key(|turn| turn.last_update_time.or(turn.create_time));
+    }
+
+    fn oldest(&self) -> Option<&Turn> {
+        self.iter().min_by_key(|turn| turn.create_time)
+    }
+
+    fn newest(&self) -> Option<&Turn> {
+        self.iter().max_by_key(|turn| turn.create_time)
+    }
+
+    fn created_between(&self, start: Timestamp, end: Timestamp) -> Vec<&Turn> {
+        self.iter().filter(|turn| turn.create_time.is_some_and(|t| start <= t && t < end)).collect()
+    }
+}
+
+impl_from_json!(ChatHistory, Chat, Candidate, Turn);
+impl_serde!(ChatHistory, Chat, Candidate, Turn);
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn chat_reads_neo_payload() {
+        let chat = Chat::try_from(&json!({
+            "chat_id": "chat",
+            "create_time": "2024-05-01T12:00:00.000Z",
+            "creator_id": "123",
+            "character_id": "char",
+            "state": "STATE_ACTIVE",
+            "type": "TYPE_ONE_ON_ONE",
+            "visibility": "VISIBILITY_PRIVATE",
+            "character_name": "Bot",
+            "character_avatar_uri": "uploaded/avatar.webp",
+            "preview_turns": [{
+                "turn_key": { "chat_id": "chat", "turn_id": "t" },
+                "author": { "author_id": "456", "name": "Bot" },
+                "candidates": [{ "candidate_id": "c", "raw_content": "Hello" }]
+            }]
+        })).unwrap();
+
+        assert_eq!(chat.id, "chat");
+        assert_eq!(chat.creator_id, "123");
+        assert_eq!(chat.visibility, Visibility::Hidden);
+        assert_eq!(chat.character_avatar, Some(Avatar::new("uploaded/avatar.webp")));
+        assert_eq!(chat.preview_turns[0].author_id, 456);
+        assert_eq!(Chat::try_from(&chat.to_json()).unwrap(), chat);
+
+        let history = ChatHistory::try_from(&json!({ "external_id": "chat", "created": "2024-05-01T12:00:00Z", "last_interaction": "" })).unwrap();
+        assert_eq!(history.create_time, chat.create_time);
+        assert_eq!(history.last_interaction_time, None);
+    }
+
+    #[test]
+    fn turn_reads_pin_state_and_edits() {
+        let turn = Turn::try_from(&json!({
+            "turn_key": { "chat_id": "chat", "turn_id": "t" },
+            "author": { "author_id": "1", "name": "Bot" },
+            "state": "STATE_DELETED",
+            "is_pinned": true,
+            "primary_candidate_id": "edited",
+            "candidates": [
+                { "candidate_id": "original", "raw_content": "Hi" },
+                { "candidate_id": "edited", "raw_content": "Hello", "base_candidate_id": "original", "editor": { "author_id": "7", "name": "Mod" }, "tti_image_rel_path": "img/1.png" }
+            ]
+        })).unwrap();
+
+        assert_eq!(turn.state, TurnState::Deleted);
+        assert!(turn.is_pinned);
+        let edited = turn.get_primary_candidate().unwrap();
+        assert!(edited.is_edited());
+        assert_eq!((edited.editor_id, edited.editor_name.as_deref()), (Some(7), Some("Mod")));
+        assert_eq!(edited.base_candidate_id.as_ref().unwrap(), "original");
+        assert_eq!(edited.image_rel_path.as_deref(), Some("img/1.png"));
+        assert!(!turn.candidates[CandidateIdRef::new("original")].is_edited());
+        assert_eq!(Turn::try_from(&turn.to_json()).unwrap(), turn);
+        assert_eq!(TurnState::from_string("STATE_NEW"), TurnState::Other("STATE_NEW".to_string()));
+    }
+
+    #[test]
+    fn turns_order_by_time() {
+        let turn = |id: &str, created: &str, updated: &str| Turn::try_from(&json!({
+            "turn_key": { "chat_id": "chat", "turn_id": id },
+            "author": { "author_id": "1", "name": "Bot" },
+            "create_time": created,
+            "last_update_time": updated
+        })).unwrap();
+        let mut turns = [
+            turn("c", "2024-05-01T12:02:00Z", "2024-05-01T12:02:00Z"),
+            turn("b", "2024-05-01T12:01:00Z", "2024-05-01T12:05:00Z"),
+            turn("a", "2024-05-01T12:00:00Z", "2024-05-01T12:00:30Z"),
+        ];
+        assert_eq!(turns[1].last_update_time.unwrap().to_string(), "2024-05-01T12:05:00Z");
+        assert_eq!(turns.newest().unwrap().id, "c");
+        assert_eq!(turns.oldest().unwrap().id, "a");
+
+        turns.sort_chronologically();
+        assert_eq!(turns.iter().map(|t| t.id.as_str()).collect::<Vec<_>>(), ["a", "b", "c"]);
+        turns.sort_by_last_update();
+        assert_eq!(turns.iter().map(|t| t.id.as_str()).collect::<Vec<_>>(), ["a", "c", "b"]);
+
+        let window = turns.created_between("2024-05-01T12:01:00Z".parse().unwrap(), "2024-05-01T12:02:00Z".parse().unwrap());
+        assert_eq!(window.len(), 1);
+        assert_eq!(window[0].id, "b");
+    }
+}
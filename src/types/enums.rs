@@ -1,5 +1,7 @@
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "lowercase"))]
 pub enum Gender {
+    #[default]
     Neutral,
     Male,
     Female
@@ -8,8 +10,8 @@ pub enum Gender {
 impl Gender {
     pub fn from_string(string: impl Into<String>) -> Self {
         match string.into() {
-            v if v == "male".to_string() => { Gender::Male },
-            v if v == "female".to_string() => { Gender::Female },
+            v if v == "male" => { Gender::Male },
+            v if v == "female" => { Gender::Female },
             _ => { Gender::Neutral }
         }
     }
@@ -23,18 +25,23 @@ impl Gender {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Visibility {
+    #[cfg_attr(feature = "serde", serde(rename = "PRIVATE"))]
     Hidden,
+    #[cfg_attr(feature = "serde", serde(rename = "UNLISTED"))]
     Unlisted,
+    #[default]
+    #[cfg_attr(feature = "serde", serde(rename = "PUBLIC", other))]
     Public
 }
 
 impl Visibility {
     pub fn from_string(string: impl Into<String>) -> Self {
         match string.into() {
-            v if v == "PRIVATE".to_string() => { Visibility::Hidden },
-            v if v == "UNLISTED".to_string() => { Visibility::Unlisted },
+            v if v == "PRIVATE" => { Visibility::Hidden },
+            v if v == "UNLISTED" => { Visibility::Unlisted },
             _ => { Visibility::Public }
         }
     }
@@ -46,4 +53,35 @@ impl Visibility {
             Visibility::Public => "PUBLIC"
         }
     }
-}
\ No newline at end of file
+}
+
+/// The state of a turn. States this crate doesn't know about are kept as they were sent.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TurnState {
+    #[default]
+    #[cfg_attr(feature = "serde", serde(rename = "STATE_OK"))]
+    Ok,
+    #[cfg_attr(feature = "serde", serde(rename = "STATE_DELETED"))]
+    Deleted,
+    #[cfg_attr(feature = "serde", serde(untagged))]
+    Other(String)
+}
+
+impl TurnState {
+    pub fn from_string(string: impl Into<String>) -> Self {
+        match string.into() {
+            v if v == "STATE_OK" => { TurnState::Ok },
+            v if v == "STATE_DELETED" => { TurnState::Deleted },
+            v => { TurnState::Other(v) }
+        }
+    }
+
+    pub fn to_string(&self) -> &str {
+        match &self {
+            TurnState::Ok => "STATE_OK",
+            TurnState::Deleted => "STATE_DELETED",
+            TurnState::Other(v) => v
+        }
+    }
+}
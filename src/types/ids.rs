@@ -0,0 +1,220 @@
+use std::{borrow::Borrow, fmt, ops::Deref};
+
+/// Declares an owned id wrapping a `String`, and a borrowed id wrapping a `str` that it derefs to, the same way
+/// `String` derefs to `str`.
+///
+/// Methods take `impl AsRef<Borrowed>`, which is implemented for both forms as well as for `str` and `String`, so
+/// callers can pass whatever they have without allocating, while an id of one kind can't be passed as another.
+macro_rules! id_types {
+    ($($(#[$meta:meta])* $owned:ident($borrowed:ident);)*) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+            #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+            pub struct $owned(String);
+
+            #[doc = concat!("A borrowed [`", stringify!($owned), "`].")]
+            #[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+            #[repr(transparent)]
+            pub struct $borrowed(str);
+
+            impl $owned {
+                pub fn new(id: impl Into<String>) -> Self {
+                    Self(id.into())
+                }
+
+                pub fn as_str(&self) -> &str {
+                    &self.0
+                }
+
+                pub fn into_string(self) -> String {
+                    self.0
+                }
+            }
+
+            impl $borrowed {
+                pub fn new(id: &str) -> &Self {
+                    // SAFETY: the borrowed id is a `repr(transparent)` wrapper around `str`.
+                    unsafe { &*(id as *const str as *const Self) }
+                }
+
+                pub fn as_str(&self) -> &str {
+                    &self.0
+                }
+            }
+
+            impl Deref for $owned {
+                type Target = $borrowed;
+
+                fn deref(&self) -> &$borrowed {
+                    $borrowed::new(&self.0)
+                }
+            }
+
+            impl Borrow<$borrowed> for $owned {
+                fn borrow(&self) -> &$borrowed {
+                    self
+                }
+            }
+
+            impl ToOwned for $borrowed {
+                type Owned = $owned;
+
+                fn to_owned(&self) -> $owned {
+                    $owned(self.0.to_string())
+                }
+            }
+
+            impl AsRef<$borrowed> for $owned {
+                fn as_ref(&self) -> &$borrowed {
+                    self
+                }
+            }
+
+            impl AsRef<$borrowed> for $borrowed {
+                fn as_ref(&self) -> &$borrowed {
+                    self
+                }
+            }
+
+            impl AsRef<$borrowed> for str {
+                fn as_ref(&self) -> &$borrowed {
+                    $borrowed::new(self)
+                }
+            }
+
+            impl AsRef<$borrowed> for String {
+                fn as_ref(&self) -> &$borrowed {
+                    $borrowed::new(self)
+                }
+            }
+
+            impl AsRef<str> for $owned {
+                fn as_ref(&self) -> &str {
+                    &self.0
+                }
+            }
+
+            impl AsRef<str> for $borrowed {
+                fn as_ref(&self) -> &str {
+                    &self.0
+                }
+            }
+
+            impl From<String> for $owned {
+                fn from(id: String) -> Self {
+                    Self(id)
+                }
+            }
+
+            impl From<&str> for $owned {
+                fn from(id: &str) -> Self {
+                    Self(id.to_string())
+                }
+            }
+
+            impl From<&String> for $owned {
+                fn from(id: &String) -> Self {
+                    Self(id.clone())
+                }
+            }
+
+            impl From<&$borrowed> for $owned {
+                fn from(id: &$borrowed) -> Self {
+                    id.to_owned()
+                }
+            }
+
+            impl From<$owned> for String {
+                fn from(id: $owned) -> Self {
+                    id.0
+                }
+            }
+
+            impl PartialEq<str> for $owned {
+                fn eq(&self, other: &str) -> bool {
+                    self.0 == other
+                }
+            }
+
+            impl PartialEq<&str> for $owned {
+                fn eq(&self, other: &&str) -> bool {
+                    self.0 == *other
+                }
+            }
+
+            impl PartialEq<String> for $owned {
+                fn eq(&self, other: &String) -> bool {
+                    &self.0 == other
+                }
+            }
+
+            impl PartialEq<$borrowed> for $owned {
+                fn eq(&self, other: &$borrowed) -> bool {
+                    self.0 == other.0
+                }
+            }
+
+            impl fmt::Display for $owned {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.write_str(&self.0)
+                }
+            }
+
+            impl fmt::Display for $borrowed {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.write_str(&self.0)
+                }
+            }
+
+            #[cfg(feature = "serde")]
+            impl serde::Serialize for $borrowed {
+                fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_str(&self.0)
+                }
+            }
+        )*
+    };
+}
+
+id_types! {
+    /// The `external_id` of a character.
+    CharacterId(CharacterIdRef);
+    ChatId(ChatIdRef);
+    TurnId(TurnIdRef);
+    CandidateId(CandidateIdRef);
+    VoiceId(VoiceIdRef);
+    /// The `external_id` of a persona. Personas are characters under the hood, but are never used as one.
+    PersonaId(PersonaIdRef);
+    /// The id of a user, as sent by the neo API.
+    UserId(UserIdRef);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn takes_chat(id: impl AsRef<ChatIdRef>) -> String {
+        id.as_ref().to_string()
+    }
+
+    #[test]
+    fn ids_borrow_from_anything_stringy() {
+        let id = ChatId::new("chat");
+        assert_eq!(takes_chat(&id), "chat");
+        assert_eq!(takes_chat("chat"), "chat");
+        let owned = String::from("chat");
+        assert_eq!(takes_chat(&owned), "chat");
+        assert_eq!(takes_chat(&*id), "chat");
+
+        let turns = HashMap::from([(TurnId::from("t"), 1)]);
+        assert_eq!(turns.get(TurnIdRef::new("t")), Some(&1));
+        #[cfg(feature = "serde")]
+        {
+            assert_eq!(serde_json::to_value(&id).unwrap(), "chat");
+            assert_eq!(serde_json::json!({ "id": &*id }), serde_json::json!({ "id": "chat" }));
+        }
+    }
+}
@@ -1,27 +1,39 @@
 use serde_json::{json, Value};
 
-use crate::types::enums::*;
+use crate::types::{enums::*, ids::*, parse::{impl_from_json, impl_serde, JsonReader}, timestamp::Timestamp};
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct Avatar {
     pub file_name: String,
 }
 
+impl Default for Avatar {
+    fn default() -> Self {
+        Self::new("")
+    }
+}
+
 impl Avatar {
     pub fn new(file_name: impl Into<String>) -> Self {
         Self { file_name: file_name.into() }
     }
 
-    pub fn default() -> Self {
-        Self::new("")
+    pub fn from_json(json: &Value) -> Option<Self> {
+        json.get("avatar_file_name").and_then(|v| v.as_str().map(String::from)).map(Avatar::new)
     }
 
-    pub fn from_json(json: &Value) -> Option<Self> {
-        if let Some(avi) = json.get("avatar_file_name").and_then(|v| v.as_str().map(String::from)) { Some(Avatar::new(avi)) } else { None }
+    pub(crate) fn read_json(r: &mut JsonReader, json: &Value) -> Option<Self> {
+        r.opt_str(json, "avatar_file_name").map(Avatar::new)
     }
 
     pub fn get_url(&self, size: i32, animated: bool) -> String {
-        format!("https://characterai.io/i/{}/static/avatars/{}?webp=true&anim={}", size, self.file_name, if animated { 1 } else { 0 })
+        self.get_url_on("https://characterai.io", size, animated)
+    }
+
+    /// Same as [`Avatar::get_url`], but against a custom media host (see [`crate::requester::RequesterConfig::media_url`]).
+    pub fn get_url_on(&self, media_url: &str, size: i32, animated: bool) -> String {
+        format!("{}/i/{}/static/avatars/{}?webp=true&anim={}", media_url.trim_end_matches('/'), size, self.file_name, if animated { 1 } else { 0 })
     }
 
     pub fn get_default_url(&self) -> String {
@@ -29,23 +41,24 @@ impl Avatar {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Voice {
-    pub id: String,
+    pub id: VoiceId,
     pub name: String,
     pub description: String,
     pub gender: Gender,
     pub visibility: Visibility,
     pub preview_text: String,
     pub preview_audio_uri: Option<String>,
-    pub creator_id: Option<String>,
+    pub creator_id: Option<UserId>,
     pub creator_username: Option<String>,
-    pub last_update: Option<String>,
+    pub last_update: Option<Timestamp>,
     pub internal_status: String
 }
 
 impl Voice {
-    pub fn new(id: impl Into<String>, name: impl Into<String>, description: impl Into<String>, gender: Gender, visibility: Visibility, preview_text: impl Into<String>, preview_audio_uri: Option<String>, creator_id: Option<String>, creator_username: Option<String>, last_update: Option<String>, internal_status: impl Into<String>) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(id: impl Into<VoiceId>, name: impl Into<String>, description: impl Into<String>, gender: Gender, visibility: Visibility, preview_text: impl Into<String>, preview_audio_uri: Option<String>, creator_id: Option<UserId>, creator_username: Option<String>, last_update: Option<Timestamp>, internal_status: impl Into<String>) -> Self {
         Self {
             id: id.into(),
             name: name.into(),
@@ -53,7 +66,7 @@ impl Voice {
             gender,
             visibility,
             preview_text: preview_text.into(),
-            preview_audio_uri: preview_audio_uri.into(),
+            preview_audio_uri,
             creator_id,
             creator_username,
             last_update,
@@ -61,32 +74,50 @@ impl Voice {
         }
     }
 
-    pub fn from_json(json: &Value) -> Self {
-        let blank = json!({});
-        let creator = json.get("creatorInfo").unwrap_or(&blank);
-
-        let blank = json!("");
-        println!("{:?}", json);
+    pub(crate) fn read_json(r: &mut JsonReader, json: &Value) -> Self {
+        let creator = json.get("creatorInfo").unwrap_or(&Value::Null);
+        let (creator_id, creator_username) = r.nested(creator, "creatorInfo", |r, creator| (r.opt_str(creator, "id").map(UserId::from), r.opt_str(creator, "username")));
 
         Self::new(
-            json.get("id").unwrap_or(&blank).as_str().unwrap(),
-            json.get("name").unwrap_or(&blank).as_str().unwrap(),
-            json.get("description").unwrap_or(&blank).as_str().unwrap(),
-            Gender::from_string(json.get("gender").unwrap_or(&json!("neutral")).as_str().unwrap()),
-            Visibility::from_string(json.get("visibility").unwrap_or(&json!("PRIVATE")).as_str().unwrap()),
-            json.get("preview_text").unwrap_or(&blank).as_str().unwrap(),
-            json.get("preview_audio_uri").and_then(|v| v.as_str().map(String::from)),
-            creator.get("id").and_then(|v| v.as_str().map(String::from)),
-            creator.get("username").and_then(|v| v.as_str().map(String::from)),
-            json.get("last_update").and_then(|v| v.as_str().map(String::from)),
-            json.get("internal_status").unwrap_or(&blank).as_str().unwrap(),
+            r.str(json, "id"),
+            r.str(json, "name"),
+            r.str(json, "description"),
+            Gender::from_string(r.opt_str(json, "gender").unwrap_or("neutral".to_string())),
+            Visibility::from_string(r.opt_str(json, "visibility").unwrap_or("PRIVATE".to_string())),
+            r.str(json, "preview_text"),
+            r.opt_str(json, "preview_audio_uri"),
+            creator_id,
+            creator_username,
+            r.opt_timestamp(json, "last_update"),
+            r.str(json, "internal_status"),
         )
     }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.id.as_str(),
+            "name": self.name,
+            "description": self.description,
+            "gender": self.gender.to_string(),
+            "visibility": self.visibility.to_string(),
+            "preview_text": self.preview_text,
+            "preview_audio_uri": self.preview_audio_uri,
+            "creatorInfo": {
+                "id": self.creator_id.as_ref().map(UserId::as_str),
+                "username": self.creator_username
+            },
+            "last_update": self.last_update.map(|t| t.to_string()),
+            "internal_status": self.internal_status
+        })
+    }
 }
 
+impl_from_json!(Voice);
+impl_serde!(Voice);
+
 pub enum VoiceOrId {
     Voice(Voice),
-    Id(String),
+    Id(VoiceId),
 }
 
 impl From<Voice> for VoiceOrId {
@@ -95,14 +126,26 @@ impl From<Voice> for VoiceOrId {
     }
 }
 
+impl From<VoiceId> for VoiceOrId {
+    fn from(id: VoiceId) -> Self {
+        VoiceOrId::Id(id)
+    }
+}
+
+impl From<&VoiceIdRef> for VoiceOrId {
+    fn from(id: &VoiceIdRef) -> Self {
+        VoiceOrId::Id(id.to_owned())
+    }
+}
+
 impl From<String> for VoiceOrId {
     fn from(s: String) -> Self {
-        VoiceOrId::Id(s)
+        VoiceOrId::Id(s.into())
     }
 }
 
 impl<'a> From<&'a str> for VoiceOrId {
     fn from(s: &'a str) -> Self {
-        VoiceOrId::Id(s.to_string())
+        VoiceOrId::Id(s.into())
     }
 }
\ No newline at end of file
@@ -0,0 +1,320 @@
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::types::timestamp::Timestamp;
+
+/// A field of a Character.AI response that could not be read into the expected type.
+///
+/// `field` is the dotted path to the offending value, e.g. `turn_key.chat_id` or `candidates[2].raw_content`.
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("invalid field `{field}`: {message}")]
+pub struct ParseError {
+    pub field: String,
+    pub message: String,
+}
+
+static NULL: Value = Value::Null;
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Reads fields out of a JSON object, substituting defaults for anything missing or mistyped and keeping track
+/// of every substitution that wasn't just a missing or null field.
+///
+/// The same reading code backs both the strict `TryFrom<&Value>` implementations, which fail on the first
+/// recorded issue, and the lenient `from_json_lenient` functions, which hand the issues back as warnings.
+#[derive(Default)]
+pub(crate) struct JsonReader {
+    path: Vec<String>,
+    issues: Vec<ParseError>,
+}
+
+impl JsonReader {
+    pub fn strict<T>(json: &Value, read: impl FnOnce(&mut Self, &Value) -> T) -> Result<T, ParseError> {
+        let (value, mut issues) = Self::lenient(json, read);
+        if issues.is_empty() {
+            Ok(value)
+        } else {
+            Err(issues.swap_remove(0))
+        }
+    }
+
+    pub fn lenient<T>(json: &Value, read: impl FnOnce(&mut Self, &Value) -> T) -> (T, Vec<ParseError>) {
+        let mut reader = Self::default();
+        if !json.is_object() {
+            reader.issues.push(ParseError { field: "".to_string(), message: format!("expected an object, found {}", kind(json)) });
+        }
+        let value = read(&mut reader, json);
+        (value, reader.issues)
+    }
+
+    fn field_path(&self, field: &str) -> String {
+        self.path.iter().map(String::as_str).chain(std::iter::once(field)).filter(|s| !s.is_empty()).collect::<Vec<_>>().join(".")
+    }
+
+    fn issue(&mut self, field: &str, message: impl Into<String>) {
+        let field = self.field_path(field);
+        self.issues.push(ParseError { field, message: message.into() });
+    }
+
+    fn mismatch(&mut self, field: &str, expected: &str, found: &Value) {
+        self.issue(field, format!("expected {}, found {}", expected, kind(found)));
+    }
+
+    /// The value of `field`, treating a JSON `null` the same as a missing field.
+    fn get<'v>(json: &'v Value, field: &str) -> Option<&'v Value> {
+        json.get(field).filter(|v| !v.is_null())
+    }
+
+    pub fn str(&mut self, json: &Value, field: &str) -> String {
+        self.opt_str(json, field).unwrap_or_default()
+    }
+
+    pub fn opt_str(&mut self, json: &Value, field: &str) -> Option<String> {
+        match Self::get(json, field)? {
+            Value::String(s) => Some(s.clone()),
+            v => {
+                self.mismatch(field, "a string", v);
+                None
+            }
+        }
+    }
+
+    pub fn bool(&mut self, json: &Value, field: &str, default: bool) -> bool {
+        match Self::get(json, field) {
+            None => default,
+            Some(Value::Bool(b)) => *b,
+            Some(v) => {
+                self.mismatch(field, "a boolean", v);
+                default
+            }
+        }
+    }
+
+    pub fn i64(&mut self, json: &Value, field: &str, default: i64) -> i64 {
+        self.opt_i64(json, field).unwrap_or(default)
+    }
+
+    /// Integers are accepted either as JSON numbers or as numeric strings, since the neo API sends ids as strings.
+    pub fn opt_i64(&mut self, json: &Value, field: &str) -> Option<i64> {
+        let v = Self::get(json, field)?;
+        match v.as_i64().or_else(|| v.as_str().and_then(|s| s.parse().ok())) {
+            Some(n) => Some(n),
+            None => {
+                self.mismatch(field, "an integer", v);
+                None
+            }
+        }
+    }
+
+    /// An RFC 3339 timestamp. An empty string counts as missing, since the API sends one for times that aren't set.
+    pub fn opt_timestamp(&mut self, json: &Value, field: &str) -> Option<Timestamp> {
+        let s = self.opt_str(json, field).filter(|s| !s.is_empty())?;
+        match Timestamp::parse_rfc3339(&s) {
+            Some(t) => Some(t),
+            None => {
+                self.issue(field, format!("expected an RFC 3339 timestamp, found `{}`", s));
+                None
+            }
+        }
+    }
+
+    /// The raw value of `field`, or `null` if it's missing.
+    pub fn value(&mut self, json: &Value, field: &str) -> Value {
+        json.get(field).cloned().unwrap_or(Value::Null)
+    }
+
+    pub fn object(&mut self, json: &Value, field: &str) -> Map<String, Value> {
+        match Self::get(json, field) {
+            None => Map::new(),
+            Some(Value::Object(o)) => o.clone(),
+            Some(v) => {
+                self.mismatch(field, "an object", v);
+                Map::new()
+            }
+        }
+    }
+
+    /// An object that has to be present for the parent to make any sense, such as a turn's `turn_key`.
+    pub fn required<'v>(&mut self, json: &'v Value, field: &str) -> &'v Value {
+        match Self::get(json, field) {
+            Some(v) if v.is_object() => v,
+            Some(v) => {
+                self.mismatch(field, "an object", v);
+                &NULL
+            }
+            None => {
+                self.issue(field, "missing required field");
+                &NULL
+            }
+        }
+    }
+
+    /// Reads the object at `field` with `read`, reporting issues relative to `field`.
+    pub fn nested<T>(&mut self, json: &Value, field: &str, read: impl FnOnce(&mut Self, &Value) -> T) -> T {
+        self.path.push(field.to_string());
+        let value = read(self, json);
+        self.path.pop();
+        value
+    }
+
+    pub fn array<T>(&mut self, json: &Value, field: &str, mut read: impl FnMut(&mut Self, &Value) -> T) -> Vec<T> {
+        let items = match Self::get(json, field) {
+            None => return vec![],
+            Some(Value::Array(items)) => items,
+            Some(v) => {
+                self.mismatch(field, "an array", v);
+                return vec![];
+            }
+        };
+
+        items.iter().enumerate().map(|(i, item)| {
+            let field = format!("{}[{}]", field, i);
+            if !item.is_object() {
+                self.mismatch(&field, "an object", item);
+            }
+            self.nested(item, &field, &mut read)
+        }).collect()
+    }
+
+    pub fn str_array(&mut self, json: &Value, field: &str) -> Vec<String> {
+        let items = match Self::get(json, field) {
+            None => return vec![],
+            Some(Value::Array(items)) => items,
+            Some(v) => {
+                self.mismatch(field, "an array", v);
+                return vec![];
+            }
+        };
+
+        items.iter().enumerate().filter_map(|(i, item)| match item {
+            Value::String(s) => Some(s.clone()),
+            v => {
+                self.mismatch(&format!("{}[{}]", field, i), "a string", v);
+                None
+            }
+        }).collect()
+    }
+}
+
+/// Implements `from_json`, `from_json_lenient` and `TryFrom<&Value>` for types with a `read_json` function.
+macro_rules! impl_from_json {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl $ty {
+                /// Parses a Character.AI JSON object, falling back to defaults for missing or mistyped fields.
+                pub fn from_json(json: &serde_json::Value) -> Self {
+                    Self::from_json_lenient(json).0
+                }
+
+                /// Like [`Self::from_json`], but also returns every field that had to be substituted.
+                pub fn from_json_lenient(json: &serde_json::Value) -> (Self, Vec<$crate::types::parse::ParseError>) {
+                    $crate::types::parse::JsonReader::lenient(json, Self::read_json)
+                }
+            }
+
+            impl TryFrom<&serde_json::Value> for $ty {
+                type Error = $crate::types::parse::ParseError;
+
+                fn try_from(json: &serde_json::Value) -> Result<Self, Self::Error> {
+                    $crate::types::parse::JsonReader::strict(json, Self::read_json)
+                }
+            }
+        )*
+    };
+}
+
+pub(crate) use impl_from_json;
+
+/// Implements `Serialize` and `Deserialize` in terms of `to_json` and `TryFrom<&Value>`, so serde sees exactly
+/// the same wire format that the API sends and accepts.
+macro_rules! impl_serde {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            #[cfg(feature = "serde")]
+            impl serde::Serialize for $ty {
+                fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serde::Serialize::serialize(&self.to_json(), serializer)
+                }
+            }
+
+            #[cfg(feature = "serde")]
+            impl<'de> serde::Deserialize<'de> for $ty {
+                fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let json = <serde_json::Value as serde::Deserialize>::deserialize(deserializer)?;
+                    Self::try_from(&json).map_err(serde::de::Error::custom)
+                }
+            }
+        )*
+    };
+}
+
+pub(crate) use impl_serde;
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::types::{chat::Turn, user::Account};
+
+    #[test]
+    fn strict_parsing_reports_the_failing_field() {
+        let err = Turn::try_from(&json!({
+            "turn_key": { "chat_id": "c", "turn_id": "t" },
+            "author": { "author_id": "1", "name": "Bot" },
+            "candidates": [{ "candidate_id": "a", "raw_content": 5 }]
+        })).unwrap_err();
+        assert_eq!(err.field, "candidates[0].raw_content");
+
+        let err = Account::try_from(&json!({ "id": 1, "username": "someone" })).unwrap_err();
+        assert_eq!(err.field, "account");
+    }
+
+    #[test]
+    fn lenient_parsing_substitutes_defaults() {
+        let (account, warnings) = Account::from_json_lenient(&json!({
+            "id": "not a number",
+            "username": "someone",
+            "account": { "name": "Someone" }
+        }));
+        assert_eq!(account.id, 0);
+        assert_eq!(account.username, "someone");
+        assert_eq!(account.name, "Someone");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].field, "id");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trips_through_the_wire_format() {
+        use crate::types::{chat::Candidate, user::Persona};
+
+        let turn = Turn::try_from(&json!({
+            "turn_key": { "chat_id": "c", "turn_id": "t" },
+            "create_time": "2024-01-01T00:00:00Z",
+            "last_update_time": "2024-01-01T00:00:00Z",
+            "author": { "author_id": "42", "name": "Bot", "is_human": false },
+            "primary_candidate_id": "a",
+            "candidates": [{ "candidate_id": "a", "raw_content": "hi", "is_final": true }]
+        })).unwrap();
+        let wire = serde_json::to_value(&turn).unwrap();
+        assert_eq!(wire["turn_key"]["turn_id"], "t");
+        assert_eq!(wire["author"]["author_id"], "42");
+        assert_eq!(serde_json::from_value::<Turn>(wire).unwrap(), turn);
+
+        let persona = Persona::new("p", "Me", "Hello", "About me", "Definition", None, false, Some("me".to_string()));
+        let wire = serde_json::to_string(&persona).unwrap();
+        assert_eq!(serde_json::from_str::<Persona>(&wire).unwrap(), persona);
+
+        let err = serde_json::from_value::<Candidate>(json!({ "candidate_id": 1 })).unwrap_err();
+        assert!(err.to_string().contains("candidate_id"));
+    }
+}
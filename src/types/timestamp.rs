@@ -0,0 +1,237 @@
+use std::{fmt, str::FromStr, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use crate::types::parse::ParseError;
+
+/// Days since the epoch to a civil date, from Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + if month <= 2 { 1 } else { 0 }, month, day)
+}
+
+/// The inverse of [`civil_from_days`], from Howard Hinnant's `days_from_civil`.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * if month > 2 { month - 3 } else { month + 9 } + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// A point in time, as sent by Character.AI in RFC 3339 strings such as `2024-05-01T12:00:00.000Z`.
+///
+/// Timestamps order chronologically and are written back as RFC 3339 in UTC. With the `chrono` feature they
+/// convert to and from `chrono::DateTime<Utc>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Timestamp {
+    seconds: i64,
+    nanos: u32,
+}
+
+impl Timestamp {
+    /// `nanos` past `seconds` since the Unix epoch. Nanoseconds past a whole second carry over.
+    pub fn from_unix(seconds: i64, nanos: u32) -> Self {
+        Self { seconds: seconds + (nanos / 1_000_000_000) as i64, nanos: nanos % 1_000_000_000 }
+    }
+
+    pub fn from_unix_millis(millis: i64) -> Self {
+        Self::from_unix(millis.div_euclid(1000), millis.rem_euclid(1000) as u32 * 1_000_000)
+    }
+
+    pub fn now() -> Self {
+        SystemTime::now().into()
+    }
+
+    pub fn unix_seconds(&self) -> i64 {
+        self.seconds
+    }
+
+    pub fn unix_millis(&self) -> i64 {
+        self.seconds * 1000 + (self.nanos / 1_000_000) as i64
+    }
+
+    pub fn subsec_nanos(&self) -> u32 {
+        self.nanos
+    }
+
+    /// How long after `earlier` this is, or `None` if it's before it.
+    pub fn duration_since(&self, earlier: Timestamp) -> Option<Duration> {
+        let nanos = (self.seconds as i128 - earlier.seconds as i128) * 1_000_000_000 + self.nanos as i128 - earlier.nanos as i128;
+        u64::try_from(nanos).ok().map(Duration::from_nanos)
+    }
+
+    /// Parses an RFC 3339 timestamp, e.g. `2024-05-01T12:00:00.000Z` or `2024-05-01T14:00:00+02:00`.
+    pub fn parse_rfc3339(s: &str) -> Option<Self> {
+        let num = |range: std::ops::Range<usize>| s.get(range).filter(|d| d.bytes().all(|b| b.is_ascii_digit())).and_then(|d| d.parse::<i64>().ok());
+        let bytes = s.as_bytes();
+        if bytes.len() < 20 || bytes[4] != b'-' || bytes[7] != b'-' || !matches!(bytes[10], b'T' | b't' | b' ') || bytes[13] != b':' || bytes[16] != b':' {
+            return None;
+        }
+
+        let (year, month, day) = (num(0..4)?, num(5..7)?, num(8..10)?);
+        let (hour, minute, second) = (num(11..13)?, num(14..16)?, num(17..19)?);
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days_in_month = match month {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        };
+        if !(1..=12).contains(&month) || !(1..=days_in_month).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+
+        let mut rest = &s[19..];
+        let mut nanos = 0;
+        if let Some(fraction) = rest.strip_prefix('.') {
+            let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+            if digits == 0 {
+                return None;
+            }
+            nanos = fraction[..digits.min(9)].parse::<u32>().ok()? * 10u32.pow(9 - digits.min(9) as u32);
+            rest = &fraction[digits..];
+        }
+
+        let offset = match rest {
+            "Z" | "z" => 0,
+            _ if rest.len() == 6 && rest.as_bytes()[3] == b':' => {
+                let sign = match rest.as_bytes()[0] {
+                    b'+' => 1,
+                    b'-' => -1,
+                    _ => return None,
+                };
+                let (hours, minutes) = (num(s.len() - 5..s.len() - 3)?, num(s.len() - 2..s.len())?);
+                if hours > 23 || minutes > 59 {
+                    return None;
+                }
+                sign * (hours * 3600 + minutes * 60)
+            }
+            _ => return None,
+        };
+
+        let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset;
+        Some(Self { seconds, nanos })
+    }
+}
+
+impl fmt::Display for Timestamp {
+    /// Formats as RFC 3339 in UTC, with as many fractional digits as needed in groups of three.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (days, rem) = (self.seconds.div_euclid(86400), self.seconds.rem_euclid(86400));
+        let (year, month, day) = civil_from_days(days);
+        write!(f, "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)?;
+
+        match self.nanos {
+            0 => {}
+            n if n % 1_000_000 == 0 => write!(f, ".{:03}", n / 1_000_000)?,
+            n if n % 1_000 == 0 => write!(f, ".{:06}", n / 1_000)?,
+            n => write!(f, ".{:09}", n)?,
+        }
+        f.write_str("Z")
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_rfc3339(s).ok_or_else(|| ParseError { field: "".to_string(), message: format!("`{}` is not an RFC 3339 timestamp", s) })
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => Self::from_unix(d.as_secs() as i64, d.subsec_nanos()),
+            Err(err) => {
+                let d = err.duration();
+                match d.subsec_nanos() {
+                    0 => Self::from_unix(-(d.as_secs() as i64), 0),
+                    n => Self::from_unix(-(d.as_secs() as i64) - 1, 1_000_000_000 - n),
+                }
+            }
+        }
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(timestamp: Timestamp) -> Self {
+        let nanos = Duration::from_nanos(timestamp.nanos as u64);
+        match u64::try_from(timestamp.seconds) {
+            Ok(seconds) => UNIX_EPOCH + Duration::from_secs(seconds) + nanos,
+            Err(_) => UNIX_EPOCH - Duration::from_secs(timestamp.seconds.unsigned_abs()) + nanos,
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::DateTime<chrono::Utc>> for Timestamp {
+    fn from(time: chrono::DateTime<chrono::Utc>) -> Self {
+        Self::from_unix(time.timestamp(), time.timestamp_subsec_nanos())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<Timestamp> for chrono::DateTime<chrono::Utc> {
+    fn from(timestamp: Timestamp) -> Self {
+        chrono::DateTime::from_timestamp(timestamp.seconds, timestamp.nanos).unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Timestamp {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Timestamp {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <String as serde::Deserialize>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_formats_rfc_3339() {
+        let t: Timestamp = "2024-05-01T12:00:00.250Z".parse().unwrap();
+        assert_eq!(t.unix_millis(), 1_714_564_800_250);
+        assert_eq!(t.to_string(), "2024-05-01T12:00:00.250Z");
+        assert_eq!("2024-05-01T14:00:00.25+02:00".parse::<Timestamp>().unwrap(), t);
+        assert_eq!(Timestamp::parse_rfc3339("0001-01-01T00:00:00Z").unwrap().to_string(), "0001-01-01T00:00:00Z");
+        assert!(Timestamp::parse_rfc3339("2024-05-01").is_none());
+        assert!(Timestamp::parse_rfc3339("2024-13-01T00:00:00Z").is_none());
+        assert!(Timestamp::parse_rfc3339("2024-02-29T00:00:00Z").is_some());
+        assert!(Timestamp::parse_rfc3339("2023-02-29T00:00:00Z").is_none());
+        assert!(Timestamp::parse_rfc3339("2024-02-31T00:00:00Z").is_none());
+        assert!(Timestamp::parse_rfc3339("2024-05-01T00:00:00+99:99").is_none());
+        assert!(Timestamp::parse_rfc3339("2024-05-01T00:00:00+05:60").is_none());
+
+        let later = Timestamp::from_unix_millis(t.unix_millis() + 1500);
+        assert!(later > t);
+        assert_eq!(later.duration_since(t), Some(Duration::from_millis(1500)));
+        assert_eq!(t.duration_since(later), None);
+        assert_eq!(Timestamp::from(SystemTime::from(t)), t);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn converts_to_and_from_chrono() {
+        let t: Timestamp = "2024-05-01T12:00:00.250Z".parse().unwrap();
+        let date: chrono::DateTime<chrono::Utc> = t.into();
+        assert_eq!(date.to_rfc3339_opts(chrono::SecondsFormat::Millis, true), "2024-05-01T12:00:00.250Z");
+        assert_eq!(Timestamp::from(date), t);
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use serde_json::{json, Value};
 
-use crate::types::media::*;
+use crate::types::{ids::*, media::*, parse::{impl_from_json, impl_serde, JsonReader}};
 
 use super::character::PartialCharacter;
 
@@ -18,7 +18,24 @@ pub struct Account {
     pub is_human: bool
 }
 
+impl Default for Account {
+    fn default() -> Self {
+        Self::new(
+            0,
+            None,
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            None,
+            None,
+            None,
+            false
+        )
+    }
+}
+
 impl Account {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(id: i64, email: Option<String>, username: impl Into<String>, name: impl Into<String>, bio: impl Into<String>, avatar: Option<Avatar>, avatar_type: Option<String>, first_name: Option<String>, is_human: bool) -> Self {
         Self { 
             id,
@@ -33,35 +50,20 @@ impl Account {
         }
     }
 
-    pub fn default() -> Self {
-        Self::new(
-            0,
-            None,
-            "".to_string(),
-            "".to_string(),
-            "".to_string(),
-            None,
-            None,
-            None,
-            false
-        )
-    }
-
-    pub fn from_json(json: &Value) -> Self {
-        let acc = json.get("account").expect("account key should be present within JSON Value provided");
-
-        let blank = json!("");
+    pub(crate) fn read_json(r: &mut JsonReader, json: &Value) -> Self {
+        let acc = r.required(json, "account");
+        let (name, avatar, avatar_type) = r.nested(acc, "account", |r, acc| (r.str(acc, "name"), Avatar::read_json(r, acc), r.opt_str(acc, "avatar_type")));
 
         Self::new(
-            json.get("id").unwrap_or(&json!(0)).as_i64().unwrap(),
-            json.get("email").and_then(|v| v.as_str().map(String::from)),
-            json.get("username").unwrap_or(&blank).as_str().unwrap(),
-            acc.get("name").unwrap_or(&blank).as_str().unwrap(),
-            json.get("bio").unwrap_or(&blank).as_str().unwrap(),
-            Avatar::from_json(&acc),
-            acc.get("avatar_type").and_then(|v| v.as_str().map(String::from)),
-            json.get("first_name").and_then(|v| v.as_str().map(String::from)),
-            json.get("is_human").unwrap_or(&json!(true)).as_bool().unwrap()
+            r.i64(json, "id", 0),
+            r.opt_str(json, "email"),
+            r.str(json, "username"),
+            name,
+            r.str(json, "bio"),
+            avatar,
+            avatar_type,
+            r.opt_str(json, "first_name"),
+            r.bool(json, "is_human", true)
         )
     }
 
@@ -88,7 +90,7 @@ impl AsRef<Account> for Account {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct User {
     pub username: String,
     pub name: String,
@@ -101,6 +103,7 @@ pub struct User {
 }
 
 impl User {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(username: impl Into<String>, name: impl Into<String>, bio: impl Into<String>, avatar: Option<Avatar>, num_following: i32, num_followers: i32, characters: Vec<PartialCharacter>, subscription_type: impl Into<String>) -> Self {
         Self { 
             username: username.into(),
@@ -114,19 +117,16 @@ impl User {
         }
     }
 
-    pub fn from_json(json: &Value) -> Self {
-        let blank = json!("");
-        let blank_num = json!(0);
-
+    pub(crate) fn read_json(r: &mut JsonReader, json: &Value) -> Self {
         Self::new(
-            json.get("username").unwrap_or(&blank).as_str().unwrap(),
-            json.get("name").unwrap_or(&blank).as_str().unwrap(),
-            json.get("bio").unwrap_or(&blank).as_str().unwrap(),
-            Avatar::from_json(&json),
-            json.get("num_following").unwrap_or(&blank_num).as_i64().unwrap() as i32,
-            json.get("num_followers").unwrap_or(&blank_num).as_i64().unwrap() as i32,
-            json.get("characters").unwrap_or(&json!([])).as_array().unwrap().into_iter().map(|v| PartialCharacter::from_json(&v)).collect(),
-            json.get("subscription_type").unwrap_or(&blank).as_str().unwrap_or("NONE")
+            r.str(json, "username"),
+            r.str(json, "name"),
+            r.str(json, "bio"),
+            Avatar::read_json(r, json),
+            r.i64(json, "num_following", 0) as i32,
+            r.i64(json, "num_followers", 0) as i32,
+            r.array(json, "characters", PartialCharacter::read_json),
+            r.opt_str(json, "subscription_type").unwrap_or("NONE".to_string())
         )
     }
 
@@ -144,9 +144,9 @@ impl User {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Persona {
-    pub id: String,
+    pub id: PersonaId,
     pub name: String,
     pub greeting: String,
     pub description: String,
@@ -156,8 +156,24 @@ pub struct Persona {
     pub author_username: Option<String>
 }
 
+impl Default for Persona {
+    fn default() -> Self {
+        Self::new(
+            "".to_string(),
+            "".to_string(),
+            "Hello! This is my persona".to_string(),
+            "This is my persona.".to_string(),
+            "".to_string(),
+            None,
+            true,
+            None
+        )
+    }
+}
+
 impl Persona {
-    pub fn new(id: impl Into<String>, name: impl Into<String>, greeting: impl Into<String>, description: impl Into<String>, definition: impl Into<String>, avatar: Option<Avatar>, archived: bool, author_username: Option<String>) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(id: impl Into<PersonaId>, name: impl Into<String>, greeting: impl Into<String>, description: impl Into<String>, definition: impl Into<String>, avatar: Option<Avatar>, archived: bool, author_username: Option<String>) -> Self {
         Self {
             id: id.into(),
             name: name.into(),
@@ -170,56 +186,58 @@ impl Persona {
         }
     }
 
-    pub fn default() -> Self {
-        Self::new(
-            "".to_string(),
-            "".to_string(),
-            "Hello! This is my persona".to_string(),
-            "This is my persona.".to_string(),
-            "".to_string(),
-            None,
-            true,
-            None
-        )
-    }
-
-    pub fn from_json(json: &Value) -> Self {
-        let blank = json!("");
+    pub(crate) fn read_json(r: &mut JsonReader, json: &Value) -> Self {
+        let name = if json.get("participant__name").is_some() { r.str(json, "participant__name") } else { r.str(json, "name") };
 
         Self::new(
-            json.get("external_id").unwrap_or(&blank).as_str().unwrap(),
-            json.get("participant__name").unwrap_or(json.get("name").unwrap_or(&blank)).as_str().unwrap(),
-            json.get("greeting").unwrap_or(&blank).as_str().unwrap_or("Hello! This is my persona"),
-            json.get("description").unwrap_or(&blank).as_str().unwrap_or("This is my persona."),
-            json.get("definition").unwrap_or(&blank).as_str().unwrap(),
-            Avatar::from_json(&json),
-            json.get("archived").unwrap_or(&json!(false)).as_bool().unwrap_or(false),
-            json.get("author_username").and_then(|v| v.as_str().map(String::from))
+            r.str(json, "external_id"),
+            name,
+            r.str(json, "greeting"),
+            r.str(json, "description"),
+            r.str(json, "definition"),
+            Avatar::read_json(r, json),
+            r.bool(json, "archived", false),
+            r.opt_str(json, "author_username")
         )
     }
     
     pub fn to_json(&self) -> Value {
         json!({
-            "external_id": self.id,
+            "external_id": self.id.as_str(),
             "participant__name": self.name,
+            "greeting": self.greeting,
+            "description": self.description,
             "definition": self.definition,
             "avatar_file_name": self.avatar.as_ref().map(|a| &a.file_name),
+            "archived": self.archived,
             "author_username": self.author_username
         })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Settings {
-    pub default_persona_id: String,
+    pub default_persona_id: PersonaId,
     pub discord_settings: Value,
     pub model_preference_settings: Value,
     pub output_style_settings: Value,
-    pub persona_overrides: HashMap<String, String>
+    pub persona_overrides: HashMap<CharacterId, PersonaId>
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            default_persona_id: PersonaId::default(),
+            discord_settings: Value::Null,
+            model_preference_settings: Value::Null,
+            output_style_settings: Value::Null,
+            persona_overrides: HashMap::new()
+        }
+    }
 }
 
 impl Settings {
-    pub fn new(default_persona_id: impl Into<String>, discord_settings: Value, model_preference_settings: Value, output_style_settings: Value, persona_overrides: HashMap<String, String>) -> Self {
+    pub fn new(default_persona_id: impl Into<PersonaId>, discord_settings: Value, model_preference_settings: Value, output_style_settings: Value, persona_overrides: HashMap<CharacterId, PersonaId>) -> Self {
         Self {
             default_persona_id: default_persona_id.into(),
             discord_settings,
@@ -229,33 +247,26 @@ impl Settings {
         }
     }
 
-    pub fn default() -> Self {
-        Self {
-            default_persona_id: "".to_string(),
-            discord_settings: Value::Null,
-            model_preference_settings: Value::Null,
-            output_style_settings: Value::Null,
-            persona_overrides: HashMap::new()
-        }
-    }
-
-    pub fn from_json(json: &Value) -> Self {
+    pub(crate) fn read_json(r: &mut JsonReader, json: &Value) -> Self {
         Self::new(
-            json.get("default_persona_id").unwrap_or(&json!("")).as_str().unwrap(),
-            json.get("discordSettings").unwrap_or(&Value::Null).clone(),
-            json.get("modelPreferenceSettings").unwrap_or(&Value::Null).clone(),
-            json.get("outputStyleSettings").unwrap_or(&Value::Null).clone(),
-            json.get("personaOverrides").unwrap_or(&json!({})).as_object().unwrap().into_iter().filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string()))).collect()
+            r.str(json, "default_persona_id"),
+            r.value(json, "discordSettings"),
+            r.value(json, "modelPreferenceSettings"),
+            r.value(json, "outputStyleSettings"),
+            r.object(json, "personaOverrides").into_iter().filter_map(|(k, v)| v.as_str().map(|s| (k.into(), s.into()))).collect()
         )
     }
 
     pub fn to_json(&self) -> Value {
         json!({
-            "default_persona_id": self.default_persona_id,
+            "default_persona_id": self.default_persona_id.as_str(),
             "discordSettings": self.discord_settings,
             "modelPreferenceSettings": self.model_preference_settings,
             "outputStyleSettings": self.output_style_settings,
-            "personaOverrides": self.persona_overrides
+            "personaOverrides": self.persona_overrides.iter().map(|(k, v)| (k.to_string(), json!(v.as_str()))).collect::<serde_json::Map<_, _>>()
         })
     }
-}
\ No newline at end of file
+}
+
+impl_from_json!(Account, User, Persona, Settings);
+impl_serde!(Account, User, Persona, Settings);
@@ -0,0 +1,354 @@
+use serde_json::{json, Value};
+
+use crate::types::{chat::{Chat, Turn}, ids::*, parse::{impl_from_json, impl_serde, JsonReader}};
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TurnKey {
+    pub chat_id: ChatId,
+    pub turn_id: TurnId,
+}
+
+impl TurnKey {
+    pub fn new(chat_id: impl Into<ChatId>, turn_id: impl Into<TurnId>) -> Self {
+        Self { chat_id: chat_id.into(), turn_id: turn_id.into() }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({ "chat_id": self.chat_id.as_str(), "turn_id": self.turn_id.as_str() })
+    }
+}
+
+/// The chat a [`WsCommand::CreateChat`] should create.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NewChat {
+    pub chat_id: ChatId,
+    pub creator_id: i64,
+    pub visibility: String,
+    pub character_id: CharacterId,
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub chat_type: String,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub preferred_model_type: Option<String>,
+}
+
+impl NewChat {
+    pub fn to_json(&self) -> Value {
+        let mut json = json!({
+            "chat_id": self.chat_id.as_str(),
+            "creator_id": self.creator_id,
+            "visibility": self.visibility,
+            "character_id": self.character_id.as_str(),
+            "type": self.chat_type
+        });
+        if let Some(model_type) = &self.preferred_model_type {
+            json["preferred_model_type"] = json!(model_type);
+        }
+        json
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CreateChatPayload {
+    pub chat: NewChat,
+    pub with_greeting: bool,
+}
+
+impl CreateChatPayload {
+    pub fn to_json(&self) -> Value {
+        json!({ "chat": self.chat.to_json(), "with_greeting": self.with_greeting })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NewAuthor {
+    pub author_id: i64,
+    pub is_human: bool,
+    pub name: String,
+}
+
+impl NewAuthor {
+    pub fn to_json(&self) -> Value {
+        json!({ "author_id": self.author_id, "is_human": self.is_human, "name": self.name })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NewCandidate {
+    pub candidate_id: CandidateId,
+    pub raw_content: String,
+}
+
+impl NewCandidate {
+    pub fn to_json(&self) -> Value {
+        json!({ "candidate_id": self.candidate_id.as_str(), "raw_content": self.raw_content })
+    }
+}
+
+/// The human turn sent by [`WsCommand::CreateAndGenerateTurn`].
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NewTurn {
+    pub author: NewAuthor,
+    pub candidates: Vec<NewCandidate>,
+    pub primary_candidate_id: CandidateId,
+    pub turn_key: TurnKey,
+}
+
+impl NewTurn {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "author": self.author.to_json(),
+            "candidates": self.candidates.iter().map(NewCandidate::to_json).collect::<Vec<_>>(),
+            "primary_candidate_id": self.primary_candidate_id.as_str(),
+            "turn_key": self.turn_key.to_json()
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CreateAndGenerateTurnPayload {
+    pub character_id: CharacterId,
+    pub num_candidates: u32,
+    pub previous_annotations: Value,
+    pub selected_language: String,
+    pub tts_enabled: bool,
+    pub turn: NewTurn,
+    pub user_name: String,
+}
+
+impl CreateAndGenerateTurnPayload {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "character_id": self.character_id.as_str(),
+            "num_candidates": self.num_candidates,
+            "previous_annotations": self.previous_annotations,
+            "selected_language": self.selected_language,
+            "tts_enabled": self.tts_enabled,
+            "turn": self.turn.to_json(),
+            "user_name": self.user_name
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenerateTurnCandidatePayload {
+    pub character_id: CharacterId,
+    pub previous_annotations: Value,
+    pub selected_language: String,
+    pub tts_enabled: bool,
+    pub turn_key: TurnKey,
+    pub user_name: String,
+}
+
+impl GenerateTurnCandidatePayload {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "character_id": self.character_id.as_str(),
+            "previous_annotations": self.previous_annotations,
+            "selected_language": self.selected_language,
+            "tts_enabled": self.tts_enabled,
+            "turn_key": self.turn_key.to_json(),
+            "user_name": self.user_name
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EditTurnCandidatePayload {
+    pub new_candidate_raw_content: String,
+    pub current_candidate_id: CandidateId,
+    pub turn_key: TurnKey,
+}
+
+impl EditTurnCandidatePayload {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "new_candidate_raw_content": self.new_candidate_raw_content,
+            "current_candidate_id": self.current_candidate_id.as_str(),
+            "turn_key": self.turn_key.to_json()
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RemoveTurnsPayload {
+    pub chat_id: ChatId,
+    pub turn_ids: Vec<TurnId>,
+}
+
+impl RemoveTurnsPayload {
+    pub fn to_json(&self) -> Value {
+        json!({ "chat_id": self.chat_id.as_str(), "turn_ids": self.turn_ids.iter().map(TurnId::as_str).collect::<Vec<_>>() })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SetTurnPinPayload {
+    pub is_pinned: bool,
+    pub turn_key: TurnKey,
+}
+
+impl SetTurnPinPayload {
+    pub fn to_json(&self) -> Value {
+        json!({ "is_pinned": self.is_pinned, "turn_key": self.turn_key.to_json() })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UpdatePrimaryCandidatePayload {
+    pub candidate_id: CandidateId,
+    pub turn_key: TurnKey,
+}
+
+impl UpdatePrimaryCandidatePayload {
+    pub fn to_json(&self) -> Value {
+        json!({ "candidate_id": self.candidate_id.as_str(), "turn_key": self.turn_key.to_json() })
+    }
+}
+
+/// A command sent to the neo websocket. Commands this crate doesn't model can be sent as
+/// [`WsCommand::Unknown`], holding the whole `{"command": ..., "payload": ...}` object.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(tag = "command", content = "payload", rename_all = "snake_case"))]
+pub enum WsCommand {
+    CreateChat(CreateChatPayload),
+    CreateAndGenerateTurn(CreateAndGenerateTurnPayload),
+    GenerateTurnCandidate(GenerateTurnCandidatePayload),
+    EditTurnCandidate(EditTurnCandidatePayload),
+    RemoveTurns(RemoveTurnsPayload),
+    SetTurnPin(SetTurnPinPayload),
+    UpdatePrimaryCandidate(UpdatePrimaryCandidatePayload),
+    #[cfg_attr(feature = "serde", serde(untagged))]
+    Unknown(Value),
+}
+
+impl WsCommand {
+    /// The name of the command, e.g. `create_chat`.
+    pub fn name(&self) -> String {
+        self.to_json()["command"].as_str().unwrap_or_default().to_string()
+    }
+
+    pub fn to_json(&self) -> Value {
+        let (command, payload) = match self {
+            Self::CreateChat(payload) => ("create_chat", payload.to_json()),
+            Self::CreateAndGenerateTurn(payload) => ("create_and_generate_turn", payload.to_json()),
+            Self::GenerateTurnCandidate(payload) => ("generate_turn_candidate", payload.to_json()),
+            Self::EditTurnCandidate(payload) => ("edit_turn_candidate", payload.to_json()),
+            Self::RemoveTurns(payload) => ("remove_turns", payload.to_json()),
+            Self::SetTurnPin(payload) => ("set_turn_pin", payload.to_json()),
+            Self::UpdatePrimaryCandidate(payload) => ("update_primary_candidate", payload.to_json()),
+            Self::Unknown(json) => return json.clone(),
+        };
+        json!({ "command": command, "payload": payload })
+    }
+
+    /// The message to send for this command, tagged with `request_id` so its answers can be routed back.
+    pub fn to_message(&self, request_id: impl Into<String>) -> Value {
+        let mut message = self.to_json();
+        message["request_id"] = json!(request_id.into());
+        if message.get("origin_id").is_none() {
+            message["origin_id"] = json!("web-next");
+        }
+        message
+    }
+}
+
+/// A frame received from the neo websocket. Frames this crate doesn't model are kept as
+/// [`WsEvent::Unknown`]; known frames that can't be read are parse errors rather than unknown events.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WsEvent {
+    CreateChatResponse { chat: Chat },
+    AddTurn { turn: Turn },
+    UpdateTurn { turn: Turn },
+    RemoveTurnsResponse,
+    Ok,
+    NeoError { comment: String },
+    Unknown(Value),
+}
+
+impl WsEvent {
+    pub(crate) fn read_json(r: &mut JsonReader, json: &Value) -> Self {
+        let turn = |r: &mut JsonReader| {
+            let turn = r.required(json, "turn");
+            r.nested(turn, "turn", Turn::read_json)
+        };
+
+        match json.get("command").and_then(Value::as_str) {
+            Some("create_chat_response") => {
+                let chat = r.required(json, "chat");
+                Self::CreateChatResponse { chat: r.nested(chat, "chat", Chat::read_json) }
+            }
+            Some("add_turn") => Self::AddTurn { turn: turn(r) },
+            Some("update_turn") => Self::UpdateTurn { turn: turn(r) },
+            Some("remove_turns_response") => Self::RemoveTurnsResponse,
+            Some("ok") => Self::Ok,
+            Some("neo_error") => Self::NeoError { comment: r.str(json, "comment") },
+            _ => Self::Unknown(json.clone()),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        match self {
+            Self::CreateChatResponse { chat } => json!({ "command": "create_chat_response", "chat": chat.to_json() }),
+            Self::AddTurn { turn } => json!({ "command": "add_turn", "turn": turn.to_json() }),
+            Self::UpdateTurn { turn } => json!({ "command": "update_turn", "turn": turn.to_json() }),
+            Self::RemoveTurnsResponse => json!({ "command": "remove_turns_response" }),
+            Self::Ok => json!({ "command": "ok" }),
+            Self::NeoError { comment } => json!({ "command": "neo_error", "comment": comment }),
+            Self::Unknown(json) => json.clone(),
+        }
+    }
+
+    /// The turn carried by an `add_turn` or `update_turn` event.
+    pub fn turn(&self) -> Option<&Turn> {
+        match self {
+            Self::AddTurn { turn } | Self::UpdateTurn { turn } => Some(turn),
+            _ => None,
+        }
+    }
+}
+
+impl_from_json!(WsEvent);
+impl_serde!(WsEvent);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_and_events_use_the_neo_wire_format() {
+        let command = WsCommand::SetTurnPin(SetTurnPinPayload { is_pinned: true, turn_key: TurnKey::new("chat", "turn") });
+        let message = command.to_message("request");
+        assert_eq!(message, json!({
+            "command": "set_turn_pin",
+            "origin_id": "web-next",
+            "request_id": "request",
+            "payload": { "is_pinned": true, "turn_key": { "chat_id": "chat", "turn_id": "turn" } }
+        }));
+        #[cfg(feature = "serde")]
+        {
+            assert_eq!(serde_json::to_value(&command).unwrap(), command.to_json());
+            assert_eq!(serde_json::from_value::<WsCommand>(message).unwrap(), command);
+        }
+
+        let unknown = WsCommand::Unknown(json!({ "command": "something_new", "payload": { "x": 1 } }));
+        assert_eq!(unknown.name(), "something_new");
+        assert_eq!(unknown.to_message("request")["payload"]["x"], 1);
+
+        assert_eq!(WsEvent::try_from(&json!({ "command": "neo_error", "comment": "nope" })).unwrap(), WsEvent::NeoError { comment: "nope".to_string() });
+        assert!(matches!(WsEvent::try_from(&json!({ "command": "typing", "x": 1 })).unwrap(), WsEvent::Unknown(_)));
+        let err = WsEvent::try_from(&json!({ "command": "update_turn", "turn": { "turn_key": {} } })).unwrap_err();
+        assert_eq!(err.field, "turn.author");
+    }
+}